@@ -0,0 +1,759 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::collection::{self, IndexKind};
+use crate::ranges::RangeSet;
+use crate::{natural, os, subframe, udim};
+use crate::{AssembleOptions, Collection, CollectionOrder, Error, MatchSelection};
+use crate::{CollectionKey, DIGITS_PATTERN, SUBFRAME_PATTERN};
+use crate::{Remainder, RemainderReason};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+// Key of the collection a number in an item belongs to, its index, and the
+// end of the head and start of the tail in the item.
+type Capture = (CollectionKey, i64, usize, usize);
+
+// Captures of the numbers in an item, with the reason the item is in the
+// remainder if there are none.
+type Captures = (Vec<Capture>, RemainderReason);
+
+// An item waiting for its collections to be complete, with the key and index
+// of each collection it may belong to, the reason it is in the remainder if
+// it belongs to none, and the key of its siblings with MostVarying selection.
+type Pending = (String, Vec<(CollectionKey, i64)>, RemainderReason, String);
+
+// Head and tail of the first item of a collection, with the indexes of its
+// items.
+type Group = (String, String, Vec<i64>);
+
+// Number of items matched at a time when extending in parallel.
+#[cfg(feature = "parallel")]
+const PARALLEL_CHUNK_SIZE: usize = 1 << 14;
+
+// Incremental version of assemble_with, for items that arrive one at a time
+// or are too many to hold in memory at once:
+//
+//     let mut assembler = Assembler::new(None, &AssembleOptions::new())?;
+//     assembler.extend(items);
+//     let (collections, remainder) = assembler.finish();
+//
+// Items are kept until the collections they may belong to are complete, so
+// when items arrive grouped by directory, calling complete with each
+// directory once all of its items have been added returns its collections
+// early and keeps memory bounded by the directories still open.
+#[derive(Debug)]
+pub struct Assembler {
+    patterns: Vec<Regex>,
+    options: AssembleOptions,
+    collection_map: HashMap<CollectionKey, Group>,
+    collection_keys: Vec<CollectionKey>,
+    heads_and_tails: HashMap<(String, String), (String, String)>,
+    pending: Vec<Pending>,
+}
+
+impl Assembler {
+    // Return an assembler grouping items by the numeric component matched by
+    // patterns, or an error if a pattern is invalid or has no "index" group.
+    pub fn new(
+        patterns: Option<Vec<String>>,
+        options: &AssembleOptions,
+    ) -> Result<Assembler, Error> {
+        let patterns = match patterns {
+            Some(patterns) => crate::compile_patterns(&patterns, "index", options)?,
+            None => {
+                lazy_static! {
+                    static ref DIGITS_REGEX: Regex = Regex::new(DIGITS_PATTERN).unwrap();
+                    static ref SUBFRAME_REGEX: Regex = Regex::new(SUBFRAME_PATTERN).unwrap();
+                }
+                // The subframe pattern only matches the frame and subframe
+                // before the extensions, and every other number is matched
+                // as a frame.
+                match options.subframes {
+                    true => vec![SUBFRAME_REGEX.to_owned(), DIGITS_REGEX.to_owned()],
+                    false => vec![DIGITS_REGEX.to_owned()],
+                }
+            }
+        };
+        Ok(Assembler {
+            patterns,
+            options: options.to_owned(),
+            collection_map: HashMap::new(),
+            collection_keys: vec![],
+            heads_and_tails: HashMap::new(),
+            pending: vec![],
+        })
+    }
+
+    // Add an item to be grouped.
+    pub fn push<T: AsRef<str>>(&mut self, item: T) {
+        let captures = self.captures(item.as_ref());
+        self.record(item.as_ref().to_string(), captures);
+    }
+
+    // Add the item of a path to be grouped, as assemble_paths does.
+    pub fn push_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.push(os::encode(path.as_ref().as_os_str()));
+    }
+
+    // Return the key of the collection and the index of each number in item
+    // matched by the patterns, with the end of its head and start of its tail.
+    fn captures(&self, item: &str) -> Captures {
+        let mut item_captures = vec![];
+        let mut reason = RemainderReason::NoMatch;
+
+        for pattern in self.patterns.iter() {
+            for captures in pattern.captures_iter(item) {
+                let index_match = match captures.name("index") {
+                    Some(index_match) => index_match,
+                    None => continue,
+                };
+                // The default patterns capture the sign of a negative index
+                // in a "sign" group before it, so that a dash is only a sign
+                // after a separator, and not in names like plate-1001.exr.
+                let start = captures
+                    .name("sign")
+                    .map_or(index_match.start(), |sign| sign.start());
+                let head = &item[..start];
+
+                // Only indexes with leading zeros are padded, others are
+                // ambiguous and treated as unpadded. The padding does not
+                // include the sign of negative indexes.
+                let padding = match captures.name("padding") {
+                    Some(padding) if !padding.as_str().is_empty() => {
+                        index_match.as_str().trim_start_matches('-').chars().count() as i32
+                    }
+                    _ => 0,
+                };
+
+                // An index followed by a dot and a subframe group is stored as
+                // a fixed point subframe, such as 1001.25 as 100125.
+                let (index, kind, end) = match captures.name("subframe") {
+                    Some(subframe) if &item[index_match.end()..subframe.start()] == "." => {
+                        let precision = subframe.as_str().chars().count() as i32;
+                        let decimal = &item[start..subframe.end()];
+                        (
+                            subframe::parse_decimal(decimal, precision),
+                            IndexKind::Subframe { padding: precision },
+                            subframe.end(),
+                        )
+                    }
+                    _ => (
+                        item[start..index_match.end()].parse::<i64>().ok(),
+                        IndexKind::Frame,
+                        index_match.end(),
+                    ),
+                };
+                let index = match index {
+                    Some(i) => i,
+                    None => {
+                        if !self.options.udim && reason == RemainderReason::NoMatch {
+                            reason = RemainderReason::IndexOverflow {
+                                head: head.to_string(),
+                                tail: item[end..].to_string(),
+                                padding,
+                            };
+                        }
+                        continue;
+                    }
+                };
+
+                // UDIM tiles are always four digits, other numbers are not
+                // grouped in UDIM mode.
+                let (padding, kind) = match self.options.udim {
+                    false => (padding, kind),
+                    true if kind == IndexKind::Frame
+                        && index_match.as_str().len() == 4
+                        && udim::is_udim(index) =>
+                    {
+                        (4, IndexKind::Udim)
+                    }
+                    true => continue,
+                };
+                let tail = &item[end..];
+
+                let key = match self.options.case_sensitive {
+                    true => (head.to_string(), tail.to_string(), padding, kind),
+                    false => (head.to_lowercase(), tail.to_lowercase(), padding, kind),
+                };
+                item_captures.push((key, index, start, end));
+            }
+        }
+
+        // Numbers within a subframe, such as 1001 and 25 of sim.1001.25.bgeo,
+        // are not grouped on their own.
+        let subframes: Vec<(usize, usize)> = item_captures
+            .iter()
+            .filter(|capture| matches!(capture.0 .3, IndexKind::Subframe { .. }))
+            .map(|capture| (capture.2, capture.3))
+            .collect();
+        item_captures.retain(|capture| {
+            matches!(capture.0 .3, IndexKind::Subframe { .. })
+                || !subframes
+                    .iter()
+                    .any(|(start, end)| *start <= capture.2 && capture.3 <= *end)
+        });
+        let item_captures = match self.options.selection {
+            MatchSelection::Leftmost => item_captures
+                .into_iter()
+                .min_by_key(|capture| capture.2)
+                .into_iter()
+                .collect(),
+            MatchSelection::Rightmost => item_captures
+                .into_iter()
+                .rev()
+                .max_by_key(|capture| capture.2)
+                .into_iter()
+                .collect(),
+            MatchSelection::All => item_captures,
+            MatchSelection::MostVarying => {
+                let mut item_captures = item_captures;
+                item_captures.sort_by_key(|capture| (capture.2, capture.3));
+                item_captures
+            }
+        };
+        (item_captures, reason)
+    }
+
+    // Add item to the collections of its captures.
+    fn record(&mut self, item: String, (captures, reason): Captures) {
+        let siblings = match self.options.selection {
+            MatchSelection::MostVarying => self.siblings(&item, &captures),
+            _ => String::new(),
+        };
+        let mut item_memberships = vec![];
+        for (key, index, head_end, tail_start) in captures {
+            match self.collection_map.get_mut(&key) {
+                Some((_, _, indexes)) => indexes.push(index),
+                None => {
+                    // Collections with the same head and tail in any case
+                    // share the head and tail of the first of their items.
+                    let (head, tail) = self
+                        .heads_and_tails
+                        .entry((key.0.to_string(), key.1.to_string()))
+                        .or_insert_with(|| {
+                            (item[..head_end].to_string(), item[tail_start..].to_string())
+                        });
+                    self.collection_keys.push(key.to_owned());
+                    self.collection_map.insert(
+                        key.to_owned(),
+                        (head.to_string(), tail.to_string(), vec![index]),
+                    );
+                }
+            }
+            item_memberships.push((key, index));
+        }
+        self.pending
+            .push((item, item_memberships, reason, siblings));
+    }
+
+    // Return the key shared by items that differ only by their numbers, the
+    // text of item with each captured number replaced by a null character.
+    fn siblings(&self, item: &str, captures: &[Capture]) -> String {
+        let mut siblings = String::new();
+        let mut end = 0;
+        for (_, _, head_end, tail_start) in captures.iter() {
+            siblings.push_str(&item[end.min(*head_end)..*head_end]);
+            siblings.push('\0');
+            end = end.max(*tail_start);
+        }
+        siblings.push_str(&item[end..]);
+        match self.options.case_sensitive {
+            true => siblings,
+            false => siblings.to_lowercase(),
+        }
+    }
+
+    // Return the number of items added that are not yet in returned
+    // collections or remainder.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    // Mark every item starting with prefix as added, and return the
+    // collections with heads starting with prefix and the items starting with
+    // prefix that are not in any collection. Items that may still belong to
+    // a collection that is not complete are kept until it is.
+    pub fn complete(&mut self, prefix: &str) -> (Vec<Collection>, Vec<String>) {
+        let (collections, remainder) = self.complete_with_reasons(prefix);
+        let remainder = remainder.into_iter().map(|r| r.item).collect();
+        (collections, remainder)
+    }
+
+    // Return the collections and remainder as complete does, with the reason
+    // each item of the remainder is not in a collection.
+    pub fn complete_with_reasons(&mut self, prefix: &str) -> (Vec<Collection>, Vec<Remainder>) {
+        let prefix = match self.options.case_sensitive {
+            true => prefix.to_string(),
+            false => prefix.to_lowercase(),
+        };
+        let is_complete = |key: &CollectionKey| key.0.starts_with(&prefix);
+
+        // With MostVarying selection an item chooses between its collections
+        // once they are all complete, so collections that share items with
+        // one that is not complete are kept until it is.
+        let mut blocked: HashSet<CollectionKey> = HashSet::new();
+        if self.options.selection == MatchSelection::MostVarying {
+            let mut changed = true;
+            while changed {
+                changed = false;
+                for (_, item_memberships, _, _) in self.pending.iter() {
+                    if item_memberships
+                        .iter()
+                        .all(|(key, _)| is_complete(key) && !blocked.contains(key))
+                    {
+                        continue;
+                    }
+                    for (key, _) in item_memberships.iter() {
+                        if is_complete(key) && !blocked.contains(key) {
+                            blocked.insert(key.to_owned());
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+        let is_complete = |key: &CollectionKey| is_complete(key) && !blocked.contains(key);
+
+        for (key, (_, _, indexes)) in self.collection_map.iter_mut() {
+            if is_complete(key) {
+                indexes.sort_unstable();
+                indexes.dedup();
+            }
+        }
+
+        // Items that differ only by their numbers are siblings, and every
+        // sibling keeps the collection of the same number, the one with the
+        // most distinct values across them. Ties go to the last number. The
+        // collections are formed again from the items that chose them.
+        if self.options.selection == MatchSelection::MostVarying {
+            let is_choosing = |item_memberships: &Vec<(CollectionKey, i64)>| {
+                item_memberships.len() > 1
+                    && item_memberships.iter().all(|(key, _)| is_complete(key))
+            };
+            let mut values: HashMap<(&str, usize), HashSet<i64>> = HashMap::new();
+            for (_, item_memberships, _, siblings) in self.pending.iter() {
+                if is_choosing(item_memberships) {
+                    for (number, (_, index)) in item_memberships.iter().enumerate() {
+                        values
+                            .entry((siblings.as_str(), number))
+                            .or_default()
+                            .insert(*index);
+                    }
+                }
+            }
+            let mut chosen: HashMap<String, (usize, usize)> = HashMap::new();
+            for ((siblings, number), values) in values.iter() {
+                let rank = (values.len(), *number);
+                let best = chosen.entry(siblings.to_string()).or_insert(rank);
+                *best = rank.max(*best);
+            }
+            for (_, item_memberships, _, siblings) in self.pending.iter_mut() {
+                if is_choosing(item_memberships) {
+                    let (_, number) = chosen[siblings.as_str()];
+                    let membership = item_memberships.swap_remove(number);
+                    *item_memberships = vec![membership];
+                }
+            }
+            for (key, (_, _, indexes)) in self.collection_map.iter_mut() {
+                if is_complete(key) {
+                    indexes.clear();
+                }
+            }
+            for (_, item_memberships, _, _) in self.pending.iter() {
+                for (key, index) in item_memberships.iter() {
+                    if is_complete(key) {
+                        self.collection_map.get_mut(key).unwrap().2.push(*index);
+                    }
+                }
+            }
+            for (key, (_, _, indexes)) in self.collection_map.iter_mut() {
+                if is_complete(key) {
+                    indexes.sort_unstable();
+                    indexes.dedup();
+                }
+            }
+        }
+
+        // form collections in order of first appearance, keeping the key they
+        // were grouped by
+        let mut collections = Vec::new();
+        let mut merge_candidates: HashMap<_, Vec<_>> = HashMap::new();
+        for key in self.collection_keys.iter().filter(|key| is_complete(key)) {
+            let (head, tail, indexes) = &self.collection_map[key];
+            if indexes.is_empty() {
+                continue;
+            }
+            collections.push((
+                key,
+                Collection::new(head.to_string(), tail.to_string(), key.2, indexes.to_vec())
+                    .with_kind(key.3),
+            ));
+
+            if key.2 == 0 {
+                merge_candidates
+                    .entry((key.0.as_str(), key.1.as_str(), key.3))
+                    .or_default()
+                    .push((key, indexes));
+            }
+        }
+
+        // Merge together collections that align on padding boundaries. For
+        // example, 0998-0999 and 1000-1001 can be merged into 0998-1001. Note
+        // that only indexes within the padding width limit are merged. If a
+        // collection is entirely merged into another then it will not be
+        // included as a separate collection in the results.
+        if self.options.merge_unpadded {
+            let mut fully_merged = HashSet::new();
+            for (key, collection) in collections.iter_mut() {
+                if key.2 == 0 {
+                    continue;
+                }
+
+                let candidates =
+                    match merge_candidates.get(&(key.0.as_str(), key.1.as_str(), key.3)) {
+                        Some(candidates) => candidates,
+                        None => continue,
+                    };
+                for (candidate_key, indexes) in candidates.iter() {
+                    let merged: Vec<i64> = indexes
+                        .iter()
+                        .copied()
+                        .filter(|index| {
+                            collection::index_width(key.3.whole(*index)) == collection.padding
+                        })
+                        .collect();
+                    let merged_index_count = merged.len();
+                    collection.indexes = collection
+                        .indexes
+                        .combine(&RangeSet::from_values(merged), |a, b| a || b);
+
+                    if merged_index_count == indexes.len() {
+                        fully_merged.insert(*candidate_key);
+                    }
+                }
+            }
+
+            // filter out fully merged collections.
+            collections.retain(|(key, _)| !fully_merged.contains(key));
+        }
+
+        // Filter out collections that do not have at least as many indexes as
+        // minimum_items.
+        let mut filtered: Vec<(&CollectionKey, Collection)> = collections
+            .into_iter()
+            .filter(|(_, collection)| collection.indexes.len() >= self.options.minimum_items)
+            .collect();
+
+        // Add items that matched no pattern, or are not members of an
+        // unfiltered collection, to the remainder in input order once all of
+        // the collections they could belong to are complete. Unpadded indexes
+        // may have been merged into the padded collection matching their
+        // width.
+        let filtered_map: HashMap<&CollectionKey, &Collection> = filtered
+            .iter()
+            .map(|(key, collection)| (*key, collection))
+            .collect();
+        let merge_unpadded = self.options.merge_unpadded;
+        let contains = |key: &CollectionKey, index: &i64| match filtered_map.get(key) {
+            Some(collection) => collection.indexes.contains(*index),
+            None => false,
+        };
+        let is_member = |key: &CollectionKey, index: &i64| {
+            contains(key, index)
+                || (key.2 == 0
+                    && merge_unpadded
+                    && contains(
+                        &(
+                            key.0.to_string(),
+                            key.1.to_string(),
+                            collection::index_width(key.3.whole(*index)),
+                            key.3,
+                        ),
+                        index,
+                    ))
+        };
+        let case_sensitive = self.options.case_sensitive;
+        let collection_map = &self.collection_map;
+        let mut remainder: Vec<Remainder> = Vec::new();
+        let mut take = |item: &mut String, reason: &mut RemainderReason| {
+            remainder.push(Remainder {
+                item: std::mem::take(item),
+                reason: std::mem::replace(reason, RemainderReason::NoMatch),
+            })
+        };
+        self.pending
+            .retain_mut(|(item, item_memberships, reason, _)| {
+                if item_memberships.is_empty() {
+                    let starts_with_prefix = match case_sensitive {
+                        true => item.starts_with(&prefix),
+                        false => item.to_lowercase().starts_with(&prefix),
+                    };
+                    if starts_with_prefix {
+                        take(item, reason);
+                    }
+                    return !starts_with_prefix;
+                }
+                if item_memberships
+                    .iter()
+                    .any(|(key, index)| is_member(key, index))
+                {
+                    return false;
+                }
+
+                // The item is not in any of its complete collections, so they
+                // have too few indexes. It keeps the largest as the reason.
+                for (key, _) in item_memberships.iter().filter(|(key, _)| is_complete(key)) {
+                    let (head, tail, indexes) = &collection_map[key];
+                    let is_largest = match reason {
+                        RemainderReason::BelowMinimumItems(collection) => {
+                            indexes.len() > collection.indexes.len()
+                        }
+                        _ => true,
+                    };
+                    if is_largest {
+                        *reason = RemainderReason::BelowMinimumItems(
+                            Collection::new(
+                                head.to_string(),
+                                tail.to_string(),
+                                key.2,
+                                indexes.to_vec(),
+                            )
+                            .with_kind(key.3),
+                        );
+                    }
+                }
+                item_memberships.retain(|(key, _)| !is_complete(key));
+                if item_memberships.is_empty() {
+                    take(item, reason);
+                    return false;
+                }
+                true
+            });
+
+        // Set padding for all ambiguous collections according to the
+        // assume_padded_when_ambiguous setting.
+        if self.options.assume_padded_when_ambiguous {
+            for (_, collection) in filtered.iter_mut() {
+                if let (0, Some(first), Some(last)) = (
+                    collection.padding,
+                    collection.indexes.first(),
+                    collection.indexes.last(),
+                ) {
+                    let whole = |index: i64| collection.kind.whole(index);
+                    let first_index_width = collection::index_width(whole(first));
+                    let last_index_width = collection::index_width(whole(last));
+                    if first_index_width == last_index_width {
+                        collection.padding = first_index_width;
+                    }
+                }
+            }
+        }
+
+        let mut filtered: Vec<Collection> = filtered
+            .into_iter()
+            .map(|(_, collection)| collection)
+            .collect();
+        if self.options.order == CollectionOrder::Natural {
+            filtered.sort_by(crate::natural_collection_cmp);
+            remainder.sort_by(|a, b| natural::natural_cmp(&a.item, &b.item));
+        }
+
+        // forget the complete collections
+        self.collection_keys.retain(|key| !is_complete(key));
+        self.collection_map.retain(|key, _| !is_complete(key));
+        self.heads_and_tails.retain(|(head, tail), _| {
+            !head.starts_with(&prefix) || blocked.iter().any(|key| &key.0 == head && &key.1 == tail)
+        });
+
+        (filtered, remainder)
+    }
+
+    // Return the collections and remainder of every item not yet returned.
+    pub fn finish(mut self) -> (Vec<Collection>, Vec<String>) {
+        self.complete("")
+    }
+
+    // Return the collections and remainder as finish does, with the reason
+    // each item of the remainder is not in a collection.
+    pub fn finish_with_reasons(mut self) -> (Vec<Collection>, Vec<Remainder>) {
+        self.complete_with_reasons("")
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+impl<T: AsRef<str>> Extend<T> for Assembler {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+// Items are matched against the patterns on multiple threads a chunk at a
+// time, then added to their collections in order so the results are the same
+// as adding them one at a time.
+//
+// Grouping stays on one thread, as every item adds to maps shared by all
+// items. Grouping each chunk on its own thread and merging the groups in
+// order was tried, but merging hashes every distinct key of a chunk into the
+// shared maps again. Most numbers other than frames, such as the versions
+// and shots in test_large_set_benchmark, give a key of their own to nearly
+// every item of a chunk, so merging cost as much as grouping serially and
+// the benchmark ran more than twice as long.
+#[cfg(feature = "parallel")]
+impl<T: AsRef<str>> Extend<T> for Assembler {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let mut iter = iter.into_iter().peekable();
+        while iter.peek().is_some() {
+            let items: Vec<String> = iter
+                .by_ref()
+                .take(PARALLEL_CHUNK_SIZE)
+                .map(|item| item.as_ref().to_string())
+                .collect();
+            let captures: Vec<Captures> =
+                items.par_iter().map(|item| self.captures(item)).collect();
+            for (item, captures) in items.into_iter().zip(captures) {
+                self.record(item, captures);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assembler() {
+        let files = [
+            "a/render.1001.exr",
+            "a/render.1002.exr",
+            "a/notes.txt",
+            "b/render.1001.exr",
+            "b/render.1002.exr",
+            "b/render.1003.exr",
+        ];
+        let options = AssembleOptions::new().minimum_items(2);
+        let mut assembler = Assembler::new(None, &options).unwrap();
+        assembler.extend(files.iter().take(3));
+
+        let (collections, remainder) = assembler.complete("a/");
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].head, "a/render.");
+        assert_eq!(remainder, vec!["a/notes.txt"]);
+        assert_eq!(assembler.pending(), 0);
+
+        for file in files.iter().skip(3) {
+            assembler.push(file);
+        }
+        assert_eq!(assembler.pending(), 3);
+        let (collections, remainder) = assembler.finish();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].indexes(), vec![1001, 1002, 1003]);
+        assert!(remainder.is_empty());
+    }
+
+    #[test]
+    fn test_assembler_open_collections() {
+        // The versions of v001/render.1001.exr form a collection with head
+        // "v" that is not complete until every version has been added.
+        let files = vec![
+            "v001/render.1001.exr",
+            "v001/render.1002.exr",
+            "v002/render.1001.exr",
+            "v002/render.1002.exr",
+        ];
+        let options = AssembleOptions::new().minimum_items(2);
+        let mut assembler = Assembler::new(None, &options).unwrap();
+        assembler.extend(files.iter().take(2));
+        let (mut collections, remainder) = assembler.complete("v001/");
+        assert_eq!(collections.len(), 1);
+        assert!(remainder.is_empty());
+
+        assembler.extend(files.iter().skip(2));
+        collections.extend(assembler.complete("v002/").0);
+        assert_eq!(collections.len(), 2);
+
+        let (versions, remainder) = assembler.finish();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].head, "v");
+        assert!(remainder.is_empty());
+
+        collections.extend(versions);
+        collections.sort_by(crate::natural_collection_cmp);
+        let (expected, _) = crate::assemble_with(&files, None, &options).unwrap();
+        assert_eq!(collections, expected);
+    }
+
+    #[test]
+    fn test_assembler_with_reasons() {
+        // The render collection of v001 is complete before the version
+        // collection, which has more indexes, so the reason changes to it.
+        let files = vec!["v001/render.1001.exr", "v002/render.1001.exr", "notes.txt"];
+        let mut assembler = Assembler::new(None, &AssembleOptions::new()).unwrap();
+        assembler.extend(&files);
+        let (collections, remainder) = assembler.complete_with_reasons("v001/");
+        assert!(collections.is_empty());
+        assert!(remainder.is_empty());
+
+        let (collections, remainder) = assembler.finish_with_reasons();
+        assert!(collections.is_empty());
+        assert_eq!(remainder.len(), 3);
+        assert_eq!(remainder[0].reason, RemainderReason::NoMatch);
+        assert_eq!(remainder[1].item, "v001/render.1001.exr");
+        assert!(matches!(
+            &remainder[1].reason,
+            RemainderReason::BelowMinimumItems(collection) if collection.head == "v"
+        ));
+    }
+
+    #[test]
+    fn test_assembler_extend() {
+        let mut files = vec![];
+        for i in 0..20000 {
+            files.push(format!("shot/v{:03}/render.{:04}.exr", i % 7, i / 7));
+            if i % 11 == 0 {
+                files.push(format!("shot/v{:03}/notes_{}.txt", i % 7, i));
+            }
+        }
+        let options = AssembleOptions::new().order(CollectionOrder::FirstAppearance);
+
+        let mut pushed = Assembler::new(None, &options).unwrap();
+        for file in files.iter() {
+            pushed.push(file);
+        }
+        let mut extended = Assembler::new(None, &options).unwrap();
+        extended.extend(&files);
+        assert_eq!(extended.finish(), pushed.finish());
+    }
+
+    #[test]
+    fn test_assembler_most_varying() {
+        let files = [
+            "v001/render.1001.exr",
+            "v001/render.1002.exr",
+            "v001/render.1003.exr",
+            "v002/render.1001.exr",
+            "v002/render.1002.exr",
+            "v002/render.1003.exr",
+        ];
+        let options = AssembleOptions::new().selection(MatchSelection::MostVarying);
+        let mut assembler = Assembler::new(None, &options).unwrap();
+        assembler.extend(files.iter().take(3));
+
+        // The items of v001 may still choose the versions collection.
+        let (collections, remainder) = assembler.complete("v001/");
+        assert!(collections.is_empty());
+        assert!(remainder.is_empty());
+
+        assembler.extend(files.iter().skip(3));
+        let (collections, remainder) = assembler.finish();
+        assert!(remainder.is_empty());
+        let (expected, _) = crate::assemble_with(&files, None, &options).unwrap();
+        assert_eq!(collections, expected);
+        assert_eq!(collections.len(), 2);
+    }
+}
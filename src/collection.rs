@@ -1,219 +1,814 @@
-use regex::{Captures, Regex};
+use regex::Captures;
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use strfmt::strfmt;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use strfmt::{strfmt, FmtError};
 
-#[derive(Debug, PartialEq)]
+use crate::os;
+use crate::padding::PaddingStyle;
+use crate::ranges::{self, RangeSet};
+use crate::subframe::{self, Subframe};
+use crate::udim::{self, UdimTile};
+use crate::{CollectionMatcher, Error, FrameSet};
+
+// Items sharing a head and tail around a numeric index. Indexes are always
+// sorted in ascending order without duplicates, and stored as ranges of
+// consecutive indexes so that long sequences with few holes stay small.
+// Collections are ordered by head, tail, padding and then first index.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Collection {
-    pub head: String,
-    pub tail: String,
-    pub padding: i32,
-    pub indexes: Vec<i32>,
+    pub(crate) head: String,
+    pub(crate) tail: String,
+    pub(crate) padding: i32,
+    pub(crate) indexes: RangeSet,
+    pub(crate) kind: IndexKind,
+}
+
+// Kind of number the indexes of a collection represent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum IndexKind {
+    // Whole frame numbers, such as render.1001.exr.
+    Frame,
+    // Frames with a fractional part of padding digits, such as sim.1001.25.bgeo.
+    // Indexes are stored in units of the last fractional digit, so 1001.25 is
+    // stored as 100125.
+    Subframe { padding: i32 },
+    // UDIM texture tiles from 1001 to 1999, such as diffuse.1012.tx. Tiles
+    // are ten to a row, so 1012 is the second column of the second row.
+    Udim,
+}
+
+impl IndexKind {
+    // Return the whole frame of index, without its fractional part.
+    pub(crate) fn whole(&self, index: i64) -> i64 {
+        match self {
+            IndexKind::Frame | IndexKind::Udim => index,
+            IndexKind::Subframe { padding } => index / subframe::scale(*padding),
+        }
+    }
 }
 
 impl Collection {
-    pub fn new(head: String, tail: String, padding: i32, indexes: Vec<i32>) -> Collection {
+    // Return a collection of frame indexes, sorted with duplicates removed.
+    // Indexes wider than padding are kept, use try_new to reject them.
+    pub fn new(head: String, tail: String, padding: i32, indexes: Vec<i64>) -> Collection {
         Collection {
-            head: head,
-            tail: tail,
-            padding: padding,
-            indexes: indexes,
-        }
-    }
-
-    // Return formatted string represented collection.
-    pub fn format<T: AsRef<str>>(self: &Self, fmt: Option<T>) -> String {
-        let padding = format!("%0{}d", self.padding);
-        let start = self.indexes[0].to_string();
-        let end = self.indexes.last().unwrap().to_string();
-        let range = format!("{start}-{end}", start = start.as_str(), end = end.as_str());
-        let mut ranges = String::new();
-        let separated = self.separate();
-        if separated.len() > 1 {
-            ranges.clone_from(
-                &separated
-                    .iter()
-                    .map(|x| x.format(Some("{range}")))
-                    .collect::<Vec<String>>()
-                    .join(", "),
-            );
-        } else {
-            ranges.clone_from(&range);
+            head,
+            tail,
+            padding,
+            indexes: RangeSet::from_values(indexes),
+            kind: IndexKind::Frame,
+        }
+    }
+
+    // Return a collection as new does, or an error if the collection is
+    // padded and an index has more digits than its padding, such as 10000
+    // with padding 4.
+    pub fn try_new(
+        head: String,
+        tail: String,
+        padding: i32,
+        indexes: Vec<i64>,
+    ) -> Result<Collection, Error> {
+        Collection::new(head, tail, padding, indexes).check_padding()
+    }
+
+    // Return a collection with the head, tail, padding and kind of this one
+    // and the frames of frames, such as the frames a render is expected to
+    // produce. Frames of a subframe collection are whole frames. Returns an
+    // error if a frame is wider than the padding.
+    pub fn with_frames(&self, frames: &FrameSet) -> Result<Collection, Error> {
+        let scale = match self.kind {
+            IndexKind::Frame | IndexKind::Udim => 1,
+            IndexKind::Subframe { padding } => subframe::scale(padding),
+        };
+        let indexes = match scale {
+            1 => frames.frames.clone(),
+            scale => {
+                RangeSet::from_values(frames.frames.iter().map(|frame| frame * scale).collect())
+            }
+        };
+        self.with_indexes(indexes).check_padding()
+    }
+
+    // Return the collection with indexes of kind, for example to build a
+    // subframe collection from fixed point indexes.
+    pub fn with_kind(mut self, kind: IndexKind) -> Collection {
+        self.kind = kind;
+        self
+    }
+
+    // Return the collection with indexes of kind as with_kind does, or an
+    // error if an index has more whole digits than the padding.
+    pub fn try_with_kind(self, kind: IndexKind) -> Result<Collection, Error> {
+        self.with_kind(kind).check_padding()
+    }
+
+    // Return a collection with the head, tail, padding and kind of this one
+    // and indexes.
+    fn with_indexes(&self, indexes: RangeSet) -> Collection {
+        Collection {
+            head: self.head.to_string(),
+            tail: self.tail.to_string(),
+            padding: self.padding,
+            indexes,
+            kind: self.kind,
+        }
+    }
+
+    // The widest indexes are the first and last, as indexes are sorted.
+    fn check_padding(self) -> Result<Collection, Error> {
+        if self.padding > 0 {
+            let wide = [self.indexes.first(), self.indexes.last()]
+                .iter()
+                .flatten()
+                .copied()
+                .find(|index| index_width(self.kind.whole(*index)) > self.padding);
+            if let Some(index) = wide {
+                return Err(Error::IndexExceedsPadding {
+                    index: self.index_string(index),
+                    padding: self.padding,
+                });
+            }
         }
+        Ok(self)
+    }
+
+    pub fn head(&self) -> &str {
+        &self.head
+    }
+
+    pub fn tail(&self) -> &str {
+        &self.tail
+    }
+
+    // Return the number of digits indexes are zero padded to, or 0 if they
+    // are not padded.
+    pub fn padding(&self) -> i32 {
+        self.padding
+    }
+
+    // Return the indexes in ascending order. Subframe indexes are fixed
+    // point, see IndexKind::Subframe. This lists every index, so iterating
+    // over the items or using contains is cheaper for long sequences.
+    pub fn indexes(&self) -> Vec<i64> {
+        self.indexes.to_vec()
+    }
+
+    pub fn kind(&self) -> IndexKind {
+        self.kind
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+
+    // Return the number of indexes.
+    pub fn len(&self) -> usize {
+        self.indexes.len()
+    }
+
+    // Return formatted string represented collection. Returns an error if the
+    // format contains an unknown key or the collection is empty.
+    pub fn format<T: AsRef<str>>(&self, fmt: Option<T>) -> Result<String, Error> {
+        self.format_with(fmt, PaddingStyle::Printf)
+    }
+
+    // Return the collection formatted as format does, with {padding} written
+    // in style, such as #### for PaddingStyle::Hash. Returns an error if the
+    // style cannot write the indexes of the collection.
+    pub fn format_with<T: AsRef<str>>(
+        &self,
+        fmt: Option<T>,
+        style: PaddingStyle,
+    ) -> Result<String, Error> {
+        let (first, last) = match (self.indexes.first(), self.indexes.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(Error::EmptyCollection),
+        };
+        let (start, end) = (self.index_string(first), self.index_string(last));
+        let padding = self.padding_token(style)?;
+        let step = self.step();
+        let range = match first == last {
+            true => start.to_string(),
+            false => format!(
+                "{}-{}{}{}",
+                start,
+                end,
+                self.range_step(step),
+                self.step_suffix(step)
+            ),
+        };
+        let ranges = self.ranges_string(&self.indexes, step);
+        // Blender writes the frame straight after the name, so the dot at the
+        // end of the head is dropped before a Blender token.
+        let blender_head = self.head.strip_suffix('.').unwrap_or(&self.head);
 
         match fmt {
             Some(fmt) => {
                 let mut vars = HashMap::new();
-                let mut holes = String::new();
+                let holes;
                 if fmt.as_ref().contains("{holes}") {
-                    holes.clone_from(&self.holes().format(Some("{range}")));
+                    holes = self.ranges_string(&self.holes().indexes, step);
                     vars.insert("holes".to_string(), holes.as_str());
                 }
 
                 if fmt.as_ref().contains("{range}") || fmt.as_ref().contains("{ranges}") {
-                    let indexes_count = self.indexes.len();
-                    if indexes_count == 0 {
-                        vars.insert("range".to_string(), "");
-                    } else if indexes_count == 1 {
-                        vars.insert("range".to_string(), start.as_str());
-                    } else {
-                        vars.insert("range".to_string(), range.as_str());
-                    }
+                    vars.insert("range".to_string(), range.as_str());
                 }
                 if fmt.as_ref().contains("{ranges}") {
                     vars.insert("ranges".to_string(), ranges.as_str());
                 }
 
+                // Each padding style has a key of its own, such as {hash}.
+                let tokens = PaddingStyle::ALL
+                    .iter()
+                    .filter(|style| fmt.as_ref().contains(&format!("{{{}}}", style.key())))
+                    .map(|style| Ok((style.key().to_string(), self.padding_token(*style)?)))
+                    .collect::<Result<Vec<(String, String)>, Error>>()?;
+                for (key, token) in tokens.iter() {
+                    vars.insert(key.to_string(), token.as_str());
+                }
+
                 vars.insert("head".to_string(), self.head.as_str());
                 vars.insert("padding".to_string(), padding.as_str());
                 vars.insert("tail".to_string(), self.tail.as_str());
                 vars.insert("start".to_string(), start.as_str());
                 vars.insert("end".to_string(), end.as_str());
+                let step = self.index_string(step);
+                vars.insert("step".to_string(), step.as_str());
+                if self.kind == IndexKind::Udim {
+                    vars.insert("udim".to_string(), udim::UDIM_TOKEN);
+                    vars.insert("uvtile".to_string(), udim::UVTILE_TOKEN);
+                }
 
-                match strfmt(&fmt.as_ref(), &vars) {
-                    Ok(string) => string,
-                    Err(_) => "".to_string(),
+                let mut blender_fmt = fmt
+                    .as_ref()
+                    .replace("{head}{blender}", "{blender_head}{blender}");
+                if style == PaddingStyle::Blender {
+                    blender_fmt = blender_fmt.replace("{head}{padding}", "{blender_head}{padding}");
+                }
+                if blender_fmt != fmt.as_ref() {
+                    vars.insert("blender_head".to_string(), blender_head);
                 }
+                strfmt(&blender_fmt, &vars).map_err(|error| format_error(fmt.as_ref(), error))
             }
-            None => format!(
+            None => Ok(format!(
                 "{head}{padding}{tail} [{ranges}]",
-                head = self.head.as_str(),
+                head = match style {
+                    PaddingStyle::Blender => blender_head,
+                    _ => self.head.as_str(),
+                },
                 padding = padding.as_str(),
                 tail = self.tail.as_str(),
                 ranges = ranges.as_str(),
-            ),
+            )),
         }
     }
 
-    pub fn match_item<'t>(self: &Self, item: &'t String) -> Option<Captures<'t>> {
-        let regex_str = format!(
-            "^{0}(?P<index>(?P<padding>0*)\\d+?){1}$",
-            self.head, self.tail
-        );
-        let compiled_regex: Regex = Regex::new(regex_str.as_str()).unwrap();
-        let regex_match = compiled_regex.captures(item);
-        match regex_match {
-            None => return None,
-            Some(capture) => {
-                let index = capture.name("index").unwrap().as_str();
-                let mut padded = false;
-                match capture.name("padding") {
-                    Some(_) => padded = true,
-                    None => (),
-                };
-
-                if self.padding == 0 && padded {
-                    return None;
-                }
-                if index.chars().count() != self.padding as usize {
-                    return None;
-                }
-                Some(capture)
+    // Return the padding written in style, such as %04d or ####. RV tokens
+    // start with the range of the indexes, such as 1001-1100#.
+    fn padding_token(&self, style: PaddingStyle) -> Result<String, Error> {
+        let range = match (self.indexes.first(), self.indexes.last()) {
+            (Some(first), Some(last)) => {
+                format!("{}-{}{}", first, last, self.range_step(self.step()))
             }
+            _ => String::new(),
+        };
+        style.token(self.padding, self.kind, &range)
+    }
+
+    // Return index as it is displayed in formatted ranges, such as 1001 for
+    // frames or 1001.25 for subframes.
+    fn index_string(&self, index: i64) -> String {
+        match self.kind {
+            IndexKind::Frame | IndexKind::Udim => index.to_string(),
+            IndexKind::Subframe { padding } => Subframe::from_index(index, padding).to_string(),
+        }
+    }
+
+    // Return the step shown after subframe ranges, such as " @0.25".
+    fn step_suffix(&self, step: i64) -> String {
+        match self.kind {
+            IndexKind::Frame | IndexKind::Udim => String::new(),
+            IndexKind::Subframe { .. } => format!(" @{}", self.index_string(step)),
         }
     }
 
-    // Return whether an item exists within the collection
-    pub fn contains(self: &Self, item: &String) -> bool {
-        for i in self.into_iter() {
-            if i == *item {
-                return true;
+    // Return the step shown after each range of frames, such as "x2" for
+    // every other frame. Frames with a step of 1 have none.
+    fn range_step(&self, step: i64) -> String {
+        match (self.kind, step) {
+            (IndexKind::Frame, step) if step > 1 => format!("x{}", step),
+            _ => String::new(),
+        }
+    }
+
+    // Return indexes formatted as comma separated ranges of indexes a step
+    // apart, such as "1001-1003, 1005" or "1001-1099x2", followed by the step
+    // for subframes. Two frames more than 1 apart are listed separately
+    // rather than as a range.
+    fn ranges_string(&self, indexes: &RangeSet, step: i64) -> String {
+        let mut ranges = vec![];
+        let mut has_range = false;
+        let range_step = self.range_step(step);
+        for (start, end) in runs(indexes, step) {
+            if start == end {
+                ranges.push(self.index_string(start));
+            } else if !range_step.is_empty() && end == start + step {
+                ranges.push(self.index_string(start));
+                ranges.push(self.index_string(end));
+            } else {
+                has_range = true;
+                ranges.push(format!(
+                    "{}-{}{}",
+                    self.index_string(start),
+                    self.index_string(end),
+                    range_step
+                ));
             }
         }
-        false
+        let mut ranges = ranges.join(", ");
+        if has_range {
+            ranges.push_str(&self.step_suffix(step));
+        }
+        ranges
     }
 
-    // Return whether entire collection is contiguous.
-    pub fn is_contiguous(self: &Self) -> bool {
-        let mut previous = None;
-        for index in self.indexes.iter() {
-            match previous {
-                None => {
-                    previous = Some(index);
-                    continue;
-                }
-                Some(v) => {
-                    if *index != (v + 1) {
-                        return false;
-                    }
-                }
+    // Return the difference between consecutive indexes that are not holes.
+    // This is the greatest common divisor of the differences between
+    // indexes, so 2 for a render of every other frame. A step is only found
+    // between at least three indexes, so fewer frames have a step of 1 and
+    // two frames far apart have holes between them. Tiles always have a step
+    // of 1.
+    pub fn step(&self) -> i64 {
+        let scale = match self.kind {
+            IndexKind::Frame => 1,
+            IndexKind::Udim => return 1,
+            IndexKind::Subframe { padding } => subframe::scale(padding),
+        };
+        // Any run of consecutive indexes makes the step 1, otherwise it
+        // divides the step of every run and the differences between them.
+        let runs = self.indexes.runs();
+        if runs
+            .iter()
+            .any(|(start, end, step)| *step == 1 && start != end)
+        {
+            return 1;
+        }
+        if self.indexes.len() < 3 {
+            return scale;
+        }
+        let step = runs
+            .iter()
+            .filter(|(start, end, _)| start != end)
+            .fold(0, |step, run| gcd(step, run.2));
+        let step = runs
+            .windows(2)
+            .fold(step, |step, pair| gcd(step, pair[1].0 - pair[0].1));
+        match step {
+            0 => scale,
+            step => step,
+        }
+    }
+
+    // Return the indexes of a subframe collection split into whole frames and
+    // fractional parts.
+    pub fn subframes(&self) -> Vec<Subframe> {
+        let precision = match self.kind {
+            IndexKind::Frame | IndexKind::Udim => 0,
+            IndexKind::Subframe { padding } => padding,
+        };
+        self.indexes
+            .iter()
+            .map(|index| Subframe::from_index(index, precision))
+            .collect()
+    }
+
+    // Return the indexes of a UDIM collection as tiles, skipping any index
+    // that is not a tile number.
+    pub fn tiles(&self) -> Vec<UdimTile> {
+        self.indexes
+            .iter()
+            .filter_map(UdimTile::from_udim)
+            .collect()
+    }
+
+    // Return the first and last u column of the tiles, or None if there are
+    // no tiles.
+    pub fn tile_columns(&self) -> Option<(i64, i64)> {
+        let tiles = self.tiles();
+        let first = tiles.iter().map(|tile| tile.u).min()?;
+        let last = tiles.iter().map(|tile| tile.u).max()?;
+        Some((first, last))
+    }
+
+    // Return the first and last v row of the tiles, or None if there are no
+    // tiles.
+    pub fn tile_rows(&self) -> Option<(i64, i64)> {
+        let tiles = self.tiles();
+        let first = tiles.iter().map(|tile| tile.v).min()?;
+        let last = tiles.iter().map(|tile| tile.v).max()?;
+        Some((first, last))
+    }
+
+    // Return the paths of the items of the collection. Heads and tails of
+    // collections from assemble_paths with bytes that are not valid UTF-8 are
+    // converted back to those bytes.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.into_iter()
+            .map(|item| PathBuf::from(os::decode(&item)))
+            .collect()
+    }
+
+    // Return whether the item with path exists within the collection.
+    pub fn contains_path<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.contains(&os::encode(path.as_ref().as_os_str()))
+    }
+
+    // Return the collection formatted as format does, with any bytes that are
+    // not valid UTF-8 in the head and tail converted back to those bytes.
+    pub fn format_os<T: AsRef<str>>(&self, fmt: Option<T>) -> Result<OsString, Error> {
+        self.format_os_with(fmt, PaddingStyle::Printf)
+    }
+
+    // Return the collection formatted as format_with does, with any bytes
+    // that are not valid UTF-8 in the head and tail converted back to those
+    // bytes.
+    pub fn format_os_with<T: AsRef<str>>(
+        &self,
+        fmt: Option<T>,
+        style: PaddingStyle,
+    ) -> Result<OsString, Error> {
+        Ok(os::decode(&self.format_with(fmt, style)?))
+    }
+
+    // Return a compiled matcher for items with the head, tail and padding of
+    // the collection. Prefer reusing a matcher over repeated calls to
+    // match_item when checking many items.
+    pub fn matcher(&self) -> Result<CollectionMatcher, Error> {
+        CollectionMatcher::new(self)
+    }
+
+    // Return the captures of item if it has the head, tail and padding of the
+    // collection.
+    pub fn match_item<'t>(&self, item: &'t str) -> Result<Option<Captures<'t>>, Error> {
+        Ok(self.matcher()?.match_item(item))
+    }
+
+    // Split items into those that match the collection and those that do not,
+    // compiling the matcher once for the whole list.
+    pub fn match_items<'t, T: AsRef<str>>(
+        &self,
+        items: &'t [T],
+    ) -> Result<(Vec<&'t str>, Vec<&'t str>), Error> {
+        Ok(self.matcher()?.match_items(items))
+    }
+
+    // Add the index of item to the collection. Returns an error if item does
+    // not have the head, tail and padding of the collection.
+    pub fn add(&mut self, item: &str) -> Result<(), Error> {
+        let index = self.match_index(item)?;
+        self.indexes.insert(index);
+        Ok(())
+    }
+
+    // Remove the index of item from the collection. Returns an error if item
+    // does not have the head, tail and padding of the collection, or its
+    // index is not in the collection.
+    pub fn remove(&mut self, item: &str) -> Result<(), Error> {
+        let index = self.match_index(item)?;
+        match self.indexes.remove(index) {
+            true => Ok(()),
+            false => Err(Error::MissingItem(item.to_string())),
+        }
+    }
+
+    // Return whether other has the same head, tail, padding and kind of
+    // index, so the two can be merged.
+    pub fn is_compatible(&self, other: &Collection) -> bool {
+        self.head == other.head
+            && self.tail == other.tail
+            && self.padding == other.padding
+            && self.kind == other.kind
+    }
+
+    // Add the indexes of other to the collection. Returns an error if other is
+    // not compatible.
+    pub fn merge(&mut self, other: &Collection) -> Result<(), Error> {
+        if !self.is_compatible(other) {
+            return Err(incompatible_collection(other));
+        }
+        self.indexes = self.indexes.combine(&other.indexes, |a, b| a || b);
+        Ok(())
+    }
+
+    // Return a collection of the indexes in either collection. Returns an
+    // error if other is not compatible.
+    pub fn union(&self, other: &Collection) -> Result<Collection, Error> {
+        self.compatible(other)?.combine(other, |a, b| a || b)
+    }
+
+    // Return a collection of the indexes in both collections. Returns an error
+    // if other is not compatible.
+    pub fn intersection(&self, other: &Collection) -> Result<Collection, Error> {
+        self.compatible(other)?.combine(other, |a, b| a && b)
+    }
+
+    // Return a collection of the indexes in this collection but not other.
+    // Returns an error if other is not compatible.
+    pub fn difference(&self, other: &Collection) -> Result<Collection, Error> {
+        self.compatible(other)?.combine(other, |a, b| a && !b)
+    }
+
+    // Return a collection of the indexes in exactly one of the collections.
+    // Returns an error if other is not compatible.
+    pub fn symmetric_difference(&self, other: &Collection) -> Result<Collection, Error> {
+        self.compatible(other)?.combine(other, |a, b| a != b)
+    }
+
+    // Return the union of the indexes of the collections as union does,
+    // whatever the head, tail and padding of other, such as to compare
+    // versions. The result has the head, tail and padding of this collection,
+    // and an error is returned if an index of other is wider than its padding
+    // or the collections have different kinds of index.
+    pub fn union_indexes(&self, other: &Collection) -> Result<Collection, Error> {
+        self.same_kind(other)?.combine(other, |a, b| a || b)
+    }
+
+    // Return the intersection of the indexes of the collections, whatever the
+    // head, tail and padding of other, as union_indexes does.
+    pub fn intersection_indexes(&self, other: &Collection) -> Result<Collection, Error> {
+        self.same_kind(other)?.combine(other, |a, b| a && b)
+    }
+
+    // Return the difference of the indexes of the collections, whatever the
+    // head, tail and padding of other, as union_indexes does. For example the
+    // frames of v002 that are not in v001.
+    pub fn difference_indexes(&self, other: &Collection) -> Result<Collection, Error> {
+        self.same_kind(other)?.combine(other, |a, b| a && !b)
+    }
+
+    // Return the symmetric difference of the indexes of the collections,
+    // whatever the head, tail and padding of other, as union_indexes does.
+    pub fn symmetric_difference_indexes(&self, other: &Collection) -> Result<Collection, Error> {
+        self.same_kind(other)?.combine(other, |a, b| a != b)
+    }
+
+    fn compatible(&self, other: &Collection) -> Result<&Collection, Error> {
+        match self.is_compatible(other) {
+            true => Ok(self),
+            false => Err(incompatible_collection(other)),
+        }
+    }
+
+    fn same_kind(&self, other: &Collection) -> Result<&Collection, Error> {
+        match self.kind == other.kind {
+            true => Ok(self),
+            false => Err(incompatible_collection(other)),
+        }
+    }
+
+    // Return a collection with the head, tail and padding of this collection
+    // and the indexes for which keep returns true, given whether each is in
+    // this collection and other.
+    fn combine(
+        &self,
+        other: &Collection,
+        keep: impl Fn(bool, bool) -> bool,
+    ) -> Result<Collection, Error> {
+        self.with_indexes(self.indexes.combine(&other.indexes, keep))
+            .check_padding()
+    }
+
+    // Return the index of item if match_item matches it, whether or not the
+    // index is in the collection.
+    fn match_index(&self, item: &str) -> Result<i64, Error> {
+        let incompatible = || Error::IncompatibleItem(item.to_string());
+        let captures = self.match_item(item)?.ok_or_else(incompatible)?;
+        let index = captures.name("index").ok_or_else(incompatible)?;
+        let index = match (self.kind, captures.name("subframe")) {
+            (IndexKind::Subframe { padding }, Some(subframe)) => {
+                subframe::parse_decimal(&item[index.start()..subframe.end()], padding)
             }
-            previous = Some(index);
+            _ => index.as_str().parse::<i64>().ok(),
+        };
+        index.ok_or_else(incompatible)
+    }
+
+    // Return whether an item exists within the collection. The head and tail
+    // are compared by exact case, even for collections assembled with
+    // case_sensitive(false), whose members are written in the case of the
+    // first item of the collection.
+    pub fn contains(&self, item: &str) -> bool {
+        let number = match item
+            .strip_prefix(self.head.as_str())
+            .and_then(|item| item.strip_suffix(self.tail.as_str()))
+        {
+            Some(number) => number,
+            None => return false,
+        };
+        let index = match self.kind {
+            IndexKind::Frame | IndexKind::Udim => number.parse::<i64>().ok(),
+            IndexKind::Subframe { padding } => subframe::parse_decimal(number, padding),
+        };
+        // The item must be written exactly as the member with its index is.
+        match index {
+            Some(index) => {
+                pad_index(index, self.padding, self.kind) == number && self.indexes.contains(index)
+            }
+            None => false,
+        }
+    }
+
+    // Return whether entire collection is contiguous. A UDIM collection is
+    // contiguous when it fills the grid of rows and columns its tiles span.
+    pub fn is_contiguous(&self) -> bool {
+        if self.kind == IndexKind::Udim {
+            return self.holes().is_empty();
+        }
+        runs(&self.indexes, self.step()).len() <= 1
+    }
+
+    // Return holes in collection, the indexes missing between its first and
+    // last index a step apart, so the holes of 1001, 1003 and 1009 are 1005
+    // and 1007. The holes of a UDIM collection are the missing tiles within
+    // the grid of rows and columns its tiles span, rather than every number
+    // between its first and last tile.
+    pub fn holes(&self) -> Collection {
+        if self.kind == IndexKind::Udim {
+            return self.with_indexes(RangeSet::from_values(self.missing_tiles()));
         }
 
-        return true;
+        self.with_indexes(self.indexes.gaps(self.step()))
     }
 
-    // Return holes in collection.
-    pub fn holes(self: &Self) -> Collection {
+    // Return the tiles missing from the grid of rows and columns spanned by
+    // the tiles of a UDIM collection.
+    fn missing_tiles(&self) -> Vec<i64> {
         let mut missing = vec![];
-        let mut previous = None;
-        for index in self.indexes.iter() {
-            match previous {
-                None => {
-                    previous = Some(index);
-                    continue;
-                }
-                Some(v) => {
-                    if *index != (v + 1) {
-                        missing.extend(v + 1..*index);
+        if let (Some((first_u, last_u)), Some((first_v, last_v))) =
+            (self.tile_columns(), self.tile_rows())
+        {
+            for v in first_v..=last_v {
+                for u in first_u..=last_u {
+                    let tile = UdimTile { u, v }.to_udim();
+                    if !self.indexes.contains(tile) {
+                        missing.push(tile);
                     }
                 }
             }
-            previous = Some(index);
         }
-
-        return Collection::new(
-            self.head.to_owned(),
-            self.tail.to_owned(),
-            self.padding,
-            missing,
-        );
+        missing
     }
 
-    // Return contiguous parts of collection as separate collections.
-    fn separate(self: &Self) -> Vec<Self> {
-        let mut collections = vec![];
-        let mut start = None;
-        let mut end = None;
+    // Return contiguous parts of collection as separate collections. An empty
+    // collection has no parts.
+    pub fn separate(&self) -> Vec<Self> {
+        let step = self.step();
+        runs(&self.indexes, step)
+            .into_iter()
+            .map(|(start, end)| {
+                let run = RangeSet::from_runs([(start, end, step)]);
+                self.with_indexes(self.indexes.combine(&run, |index, run| index && run))
+            })
+            .collect()
+    }
+}
 
-        for index in self.indexes.iter() {
-            if start == None {
-                start = Some(*index);
-                end = start;
-                continue;
+// Return the runs of indexes a step apart, as the first and last index of
+// each. Indexes of runs with another step are each a run of their own.
+fn runs(indexes: &RangeSet, step: i64) -> Vec<(i64, i64)> {
+    let mut runs: Vec<(i64, i64)> = vec![];
+    let mut join = |start: i64, end: i64| match runs.last_mut() {
+        Some((_, last)) if start == *last + step => *last = end,
+        _ => runs.push((start, end)),
+    };
+    for &(start, end, run_step) in indexes.runs() {
+        if start == end || run_step == step {
+            join(start, end);
+        } else {
+            for index in (start..=end).step_by(run_step as usize) {
+                join(index, index);
             }
+        }
+    }
+    runs
+}
 
-            if *index != (end.unwrap() + 1) {
-                collections.push(Collection::new(
-                    self.head.to_string(),
-                    self.tail.to_string(),
-                    self.padding,
-                    (start.unwrap()..end.unwrap() + 1).collect(),
-                ));
+// Convert an error formatting fmt into an Error.
+pub(crate) fn format_error(fmt: &str, error: FmtError) -> Error {
+    match error {
+        FmtError::KeyError(message) => {
+            Error::UnknownFormatKey(message.trim_start_matches("Invalid key: ").to_string())
+        }
+        FmtError::Invalid(message) | FmtError::TypeError(message) => {
+            Error::InvalidFormat(fmt.to_string(), message)
+        }
+    }
+}
 
-                start = Some(*index);
-            }
+fn incompatible_collection(collection: &Collection) -> Error {
+    Error::IncompatibleCollection {
+        head: collection.head.to_string(),
+        tail: collection.tail.to_string(),
+        padding: collection.padding,
+        kind: collection.kind,
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    match b {
+        0 => a.abs(),
+        b => gcd(b, a % b),
+    }
+}
 
-            end = Some(*index);
+// Return index zero padded to padding digits, preceded by a sign if negative.
+// Subframe indexes are followed by their zero padded fractional part.
+pub(crate) fn pad_index(index: i64, padding: i32, kind: IndexKind) -> String {
+    let (whole, fraction) = match kind {
+        IndexKind::Frame | IndexKind::Udim => (index.unsigned_abs(), None),
+        IndexKind::Subframe { padding } => {
+            let scale = subframe::scale(padding) as u64;
+            (
+                index.unsigned_abs() / scale,
+                Some(format!(
+                    ".{:0>padding$}",
+                    index.unsigned_abs() % scale,
+                    padding = padding.max(0) as usize
+                )),
+            )
         }
+    };
+    let digits = format!(
+        "{:0>padding$}{}",
+        whole,
+        fraction.unwrap_or_default(),
+        padding = padding.max(0) as usize
+    );
+    match index < 0 {
+        true => format!("-{}", digits),
+        false => digits,
+    }
+}
 
-        if start == None {
-            collections.push(Collection::new(
-                self.head.to_string(),
-                self.tail.to_string(),
-                self.padding,
-                vec![],
-            ))
-        } else {
-            collections.push(Collection::new(
-                self.head.to_string(),
-                self.tail.to_string(),
-                self.padding,
-                (start.unwrap()..end.unwrap() + 1).collect(),
-            ))
+// Return the number of digits in index, excluding its sign.
+pub(crate) fn index_width(index: i64) -> i32 {
+    index.unsigned_abs().to_string().len() as i32
+}
+
+impl PartialOrd for Collection {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Collections are ordered by head, tail, padding and kind, as the indexes of
+// different kinds are not comparable, and then by their first index and the
+// rest of their indexes, compared a run at a time, so only equal collections
+// compare equal.
+impl Ord for Collection {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.head
+            .cmp(&other.head)
+            .then_with(|| self.tail.cmp(&other.tail))
+            .then_with(|| self.padding.cmp(&other.padding))
+            .then_with(|| self.kind.cmp(&other.kind))
+            .then_with(|| self.indexes.first().cmp(&other.indexes.first()))
+            .then_with(|| self.indexes.cmp(&other.indexes))
+    }
+}
+
+// Display the collection in the default format of Collection::format, such as
+// "render.%04d.exr [1001-1005]". An empty collection has no ranges, such as
+// "render.%04d.exr []".
+impl fmt::Display for Collection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.format::<&str>(None) {
+            Ok(collection) => write!(f, "{}", collection),
+            Err(_) => {
+                let padding = self
+                    .padding_token(PaddingStyle::Printf)
+                    .map_err(|_| fmt::Error)?;
+                write!(f, "{}{}{} []", self.head, padding, self.tail)
+            }
         }
+    }
+}
+
+// Parse a collection in the default format of Collection::format, as parse
+// does without a pattern.
+impl FromStr for Collection {
+    type Err = Error;
 
-        return collections;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        crate::parse::<&str>(value, None)
     }
 }
 
@@ -230,15 +825,17 @@ impl IntoIterator for Collection {
             head: self.head,
             tail: self.tail,
             padding: self.padding,
+            kind: self.kind,
         }
     }
 }
 
 pub struct IntoIteratorHelper {
-    iter: std::vec::IntoIter<i32>,
+    iter: ranges::IntoIter,
     head: String,
     tail: String,
     padding: i32,
+    kind: IndexKind,
 }
 
 impl Iterator for IntoIteratorHelper {
@@ -247,18 +844,14 @@ impl Iterator for IntoIteratorHelper {
         match self.iter.next() {
             Some(index) => {
                 let mut vars = HashMap::new();
-                let padding: usize = self.padding as usize;
-                let index = format!("{:0>padding$}", index.to_string(), padding = padding);
+                let index = pad_index(index, self.padding, self.kind);
 
                 vars.insert("head".to_string(), self.head.as_str());
                 vars.insert("tail".to_string(), self.tail.as_str());
                 vars.insert("index".to_string(), index.as_str());
 
                 let fmt = "{head}{index}{tail}";
-                match strfmt(&fmt, &vars) {
-                    Ok(string) => Some(string),
-                    Err(_) => None,
-                }
+                strfmt(fmt, &vars).ok()
             }
             None => None,
         }
@@ -268,10 +861,11 @@ impl Iterator for IntoIteratorHelper {
 // ------------------------------------------------------------------------------
 // Non consuming iterator
 pub struct IterHelper<'a> {
-    iter: ::std::slice::Iter<'a, i32>,
+    iter: ranges::Iter<'a>,
     head: String,
     tail: String,
     padding: i32,
+    kind: IndexKind,
 }
 
 // implement the IntoIterator trait for a non-consuming iterator. Iteration will
@@ -287,6 +881,7 @@ impl<'a> IntoIterator for &'a Collection {
             head: self.head.to_string(),
             tail: self.tail.to_string(),
             padding: self.padding,
+            kind: self.kind,
         }
     }
 }
@@ -299,18 +894,14 @@ impl<'a> Iterator for IterHelper<'a> {
         match self.iter.next() {
             Some(index) => {
                 let mut vars = HashMap::new();
-                let padding: usize = self.padding as usize;
-                let index = format!("{:0padding$}", index.to_string(), padding = padding);
+                let index = pad_index(index, self.padding, self.kind);
 
                 vars.insert("head".to_string(), self.head.as_str());
                 vars.insert("tail".to_string(), self.tail.as_str());
                 vars.insert("index".to_string(), index.as_str());
 
                 let fmt = "{head}{index}{tail}";
-                match strfmt(&fmt, &vars) {
-                    Ok(string) => Some(string),
-                    Err(_) => None,
-                }
+                strfmt(fmt, &vars).ok()
             }
             None => None,
         }
@@ -330,17 +921,26 @@ mod tests {
             vec![1001, 1002, 1003, 1004, 1005],
         );
 
-        assert_eq!(c.format::<&str>(None), "head.%04d.tail [1001-1005]");
         assert_eq!(
-            c.format(Some("{head}{padding}{tail} [{range}]")),
+            c.format::<&str>(None).unwrap(),
             "head.%04d.tail [1001-1005]"
         );
         assert_eq!(
-            c.format(Some("{head}{padding}{tail} [{ranges}]")),
+            c.format(Some("{head}{padding}{tail} [{range}]")).unwrap(),
             "head.%04d.tail [1001-1005]"
         );
-        assert_eq!(c.format(Some("{head}{padding}{tail}")), "head.%04d.tail");
-        assert_eq!(c.format(Some("{head}{padding}{tail} {FOO}")), "");
+        assert_eq!(
+            c.format(Some("{head}{padding}{tail} [{ranges}]")).unwrap(),
+            "head.%04d.tail [1001-1005]"
+        );
+        assert_eq!(
+            c.format(Some("{head}{padding}{tail}")).unwrap(),
+            "head.%04d.tail"
+        );
+        assert!(matches!(
+            c.format(Some("{head}{padding}{tail} {FOO}")),
+            Err(Error::UnknownFormatKey(key)) if key == "FOO"
+        ));
 
         let c = Collection::new(
             "head.".to_string(),
@@ -348,16 +948,47 @@ mod tests {
             4,
             vec![1001, 1002, 1003, 1005],
         );
-        assert_eq!(c.format(Some("{holes}")), "1004");
-        assert_eq!(c.format::<&str>(None), "head.%04d.tail [1001-1003, 1005]");
+        assert_eq!(c.format(Some("{holes}")).unwrap(), "1004");
+        assert_eq!(
+            c.format(Some("{head}{padding}{tail} [{range}] [{holes}]"))
+                .unwrap(),
+            "head.%04d.tail [1001-1005] [1004]"
+        );
+
+        assert_eq!(
+            c.format::<&str>(None).unwrap(),
+            "head.%04d.tail [1001-1003, 1005]"
+        );
         assert_eq!(
-            c.format(Some("{head}{padding}{tail} [{range}]")),
+            c.format(Some("{head}{padding}{tail} [{range}]")).unwrap(),
             "head.%04d.tail [1001-1005]"
         );
         assert_eq!(
-            c.format(Some("{head}{padding}{tail} [{ranges}]")),
+            c.format(Some("{head}{padding}{tail} [{ranges}]")).unwrap(),
             "head.%04d.tail [1001-1003, 1005]"
         );
+
+        let c = Collection::new(
+            "head.".to_string(),
+            ".tail".to_string(),
+            4,
+            vec![1001, 1002, 1004, 1005, 1008],
+        );
+        assert_eq!(c.format(Some("{holes}")).unwrap(), "1003, 1006-1007");
+
+        let c = Collection::new(
+            "head.".to_string(),
+            ".tail".to_string(),
+            4,
+            vec![1001, 1002],
+        );
+        assert_eq!(c.format(Some("{holes}")).unwrap(), "");
+
+        let c = Collection::new("head.".to_string(), ".tail".to_string(), 4, vec![]);
+        assert!(matches!(
+            c.format::<&str>(None),
+            Err(Error::EmptyCollection)
+        ));
     }
 
     #[test]
@@ -370,20 +1001,47 @@ mod tests {
         );
 
         // probably dodgy way of testing...
-        match c.match_item(&"head.1010.tail".to_string()) {
+        match c.match_item("head.1010.tail").unwrap() {
             Some(m) => assert_eq!(m.name("index").unwrap().as_str(), "1010"),
             None => assert_eq!(false, true),
         }
-        match c.match_item(&"head.10100.tail".to_string()) {
+        match c.match_item("head.10100.tail").unwrap() {
             Some(_) => assert_eq!(false, true),
             None => assert_eq!(true, true),
         }
-        match c.match_item(&"foo.1010.tail".to_string()) {
+        match c.match_item("foo.1010.tail").unwrap() {
             Some(_) => assert_eq!(false, true),
             None => assert_eq!(true, true),
         }
     }
 
+    #[test]
+    fn test_contains() {
+        let c = Collection::new(
+            "head.".to_string(),
+            ".tail".to_string(),
+            4,
+            vec![-1, 1001, 1002, 1004],
+        );
+        assert!(c.contains("head.1001.tail"));
+        assert!(c.contains("head.-0001.tail"));
+        assert!(!c.contains("head.1003.tail"));
+        assert!(!c.contains("head.01001.tail"));
+        assert!(!c.contains("head.+1001.tail"));
+        assert!(!c.contains("head.1001.tai"));
+        assert!(!c.contains("foo.1001.tail"));
+
+        let c = Collection::new("a".to_string(), "a".to_string(), 0, vec![1]);
+        assert!(c.contains("a1a"));
+        assert!(!c.contains("a"));
+
+        let c = Collection::new("sim.".to_string(), ".bgeo".to_string(), 4, vec![100125])
+            .with_kind(IndexKind::Subframe { padding: 2 });
+        assert!(c.contains("sim.1001.25.bgeo"));
+        assert!(!c.contains("sim.1001.250.bgeo"));
+        assert!(!c.contains("sim.1001.5.bgeo"));
+    }
+
     #[test]
     fn test_is_contiguous() {
         let c = Collection::new(
@@ -392,7 +1050,7 @@ mod tests {
             4,
             vec![1001, 1002, 1003, 1004, 1005],
         );
-        assert_eq!(c.is_contiguous(), true);
+        assert!(c.is_contiguous());
 
         let c = Collection::new(
             "head.".to_string(),
@@ -400,7 +1058,7 @@ mod tests {
             4,
             vec![1001, 1002, 1003, 1005],
         );
-        assert_eq!(c.is_contiguous(), false);
+        assert!(!c.is_contiguous());
     }
 
     #[test]
@@ -436,6 +1094,353 @@ mod tests {
             vec![1006, 1007, 1009],
         );
         assert_eq!(c.holes(), expected);
+
+        let c = Collection::new(
+            "head.".to_string(),
+            ".tail".to_string(),
+            4,
+            vec![1001, 1003, 1009],
+        );
+        assert_eq!(c.holes().indexes(), &[1005, 1007]);
+    }
+
+    #[test]
+    fn test_try_new() {
+        let c = Collection::try_new(
+            "head.".to_string(),
+            ".tail".to_string(),
+            4,
+            vec![1003, 1001, 1002, 1001],
+        )
+        .unwrap();
+        assert_eq!(c.indexes(), &[1001, 1002, 1003]);
+        assert_eq!(c.len(), 3);
+        assert!(c.is_contiguous());
+        assert!(c.holes().is_empty());
+
+        assert!(matches!(
+            Collection::try_new("head.".to_string(), ".tail".to_string(), 4, vec![1001, 10000]),
+            Err(Error::IndexExceedsPadding { index, padding: 4 }) if index == "10000"
+        ));
+        assert!(
+            Collection::try_new("head.".to_string(), ".tail".to_string(), 0, vec![1, 10000])
+                .is_ok()
+        );
+
+        let c = Collection::new("sim.".to_string(), ".bgeo".to_string(), 4, vec![100125]);
+        assert!(c.try_with_kind(IndexKind::Subframe { padding: 2 }).is_ok());
+    }
+
+    #[test]
+    fn test_empty() {
+        let c = Collection::try_new("head.".to_string(), ".tail".to_string(), 4, vec![]).unwrap();
+        assert!(c.is_empty());
+        assert!(c.is_contiguous());
+        assert!(c.holes().is_empty());
+        assert!(c.separate().is_empty());
+        assert!(!c.contains("head.1001.tail"));
+        assert_eq!(c.into_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_add_remove() {
+        let mut c = Collection::new(
+            "head.".to_string(),
+            ".tail".to_string(),
+            4,
+            vec![1001, 1003],
+        );
+        c.add("head.1002.tail").unwrap();
+        c.add("head.0999.tail").unwrap();
+        c.add("head.1002.tail").unwrap();
+        assert_eq!(c.indexes(), &[999, 1001, 1002, 1003]);
+        assert!(matches!(
+            c.add("head.999.tail"),
+            Err(Error::IncompatibleItem(item)) if item == "head.999.tail"
+        ));
+        assert!(matches!(
+            c.add("other.1004.tail"),
+            Err(Error::IncompatibleItem(_))
+        ));
+
+        c.remove("head.1001.tail").unwrap();
+        assert_eq!(c.indexes(), &[999, 1002, 1003]);
+        assert!(matches!(
+            c.remove("head.1001.tail"),
+            Err(Error::MissingItem(_))
+        ));
+        assert!(matches!(
+            c.remove("head.1001.other"),
+            Err(Error::IncompatibleItem(_))
+        ));
+
+        let mut c = Collection::new("sim.".to_string(), ".bgeo".to_string(), 4, vec![100100])
+            .with_kind(IndexKind::Subframe { padding: 2 });
+        c.add("sim.1001.25.bgeo").unwrap();
+        assert_eq!(c.indexes(), &[100100, 100125]);
+        assert!(c.add("sim.1001.5.bgeo").is_err());
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut c1 = Collection::new(
+            "head.".to_string(),
+            ".tail".to_string(),
+            4,
+            vec![1001, 1002],
+        );
+        let c2 = Collection::new(
+            "head.".to_string(),
+            ".tail".to_string(),
+            4,
+            vec![1002, 1005],
+        );
+        assert!(c1.is_compatible(&c2));
+        c1.merge(&c2).unwrap();
+        assert_eq!(c1.indexes(), &[1001, 1002, 1005]);
+
+        let c3 = Collection::new("head.".to_string(), ".tail".to_string(), 0, vec![1006]);
+        assert!(!c1.is_compatible(&c3));
+        assert!(matches!(
+            c1.merge(&c3),
+            Err(Error::IncompatibleCollection { padding: 0, .. })
+        ));
+        assert_eq!(c1.indexes(), &[1001, 1002, 1005]);
+
+        let c4 = c1.clone().with_kind(IndexKind::Udim);
+        let error = c1.merge(&c4).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::IncompatibleCollection {
+                padding: 4,
+                kind: IndexKind::Udim,
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "collection with head \"head.\", tail \".tail\", padding 4 and Udim indexes is not compatible"
+        );
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let requested = Collection::new(
+            "render.".to_string(),
+            ".exr".to_string(),
+            4,
+            vec![1001, 1002, 1003, 1004],
+        );
+        let rendered = Collection::new(
+            "render.".to_string(),
+            ".exr".to_string(),
+            4,
+            vec![1003, 1004, 1005],
+        );
+        let indexes = |c: Result<Collection, Error>| c.unwrap().indexes().to_vec();
+        assert_eq!(
+            indexes(requested.union(&rendered)),
+            vec![1001, 1002, 1003, 1004, 1005]
+        );
+        assert_eq!(indexes(requested.intersection(&rendered)), vec![1003, 1004]);
+        assert_eq!(indexes(requested.difference(&rendered)), vec![1001, 1002]);
+        assert_eq!(
+            indexes(requested.symmetric_difference(&rendered)),
+            vec![1001, 1002, 1005]
+        );
+
+        let v002 = Collection::new(
+            "v002/render.".to_string(),
+            ".exr".to_string(),
+            4,
+            vec![1001, 1002, 1003, 1004, 1005, 1006],
+        );
+        assert!(matches!(
+            v002.difference(&rendered),
+            Err(Error::IncompatibleCollection { .. })
+        ));
+        let missing = v002.difference_indexes(&rendered).unwrap();
+        assert_eq!(missing.head(), "v002/render.");
+        assert_eq!(missing.indexes(), &[1001, 1002, 1006]);
+        assert_eq!(
+            indexes(v002.intersection_indexes(&requested)),
+            vec![1001, 1002, 1003, 1004]
+        );
+
+        let unpadded = Collection::new("render.".to_string(), ".exr".to_string(), 0, vec![10000]);
+        assert!(matches!(
+            requested.union_indexes(&unpadded),
+            Err(Error::IndexExceedsPadding { .. })
+        ));
+        assert_eq!(
+            indexes(unpadded.symmetric_difference_indexes(&requested)),
+            vec![1001, 1002, 1003, 1004, 10000]
+        );
+    }
+
+    #[test]
+    fn test_step() {
+        let every_other: Vec<i64> = (1001..1100).step_by(2).collect();
+        let c = Collection::new("head.".to_string(), ".tail".to_string(), 4, every_other);
+        assert_eq!(c.step(), 2);
+        assert!(c.is_contiguous());
+        assert!(c.holes().is_empty());
+        assert_eq!(
+            c.format::<&str>(None).unwrap(),
+            "head.%04d.tail [1001-1099x2]"
+        );
+        assert_eq!(c.format(Some("{range} {step}")).unwrap(), "1001-1099x2 2");
+
+        let c = Collection::new(
+            "head.".to_string(),
+            ".tail".to_string(),
+            4,
+            vec![1001, 1003, 1005, 1009, 1011, 1013, 1017],
+        );
+        assert_eq!(
+            c.format(Some("[{ranges}] [{holes}]")).unwrap(),
+            "[1001-1005x2, 1009-1013x2, 1017] [1007, 1015]"
+        );
+        let parts: Vec<Vec<i64>> = c.separate().iter().map(|c| c.indexes.to_vec()).collect();
+        assert_eq!(
+            parts,
+            vec![vec![1001, 1003, 1005], vec![1009, 1011, 1013], vec![1017]]
+        );
+
+        let c = Collection::new(
+            "head.".to_string(),
+            ".tail".to_string(),
+            4,
+            vec![1001, 1011, 1021, 1041],
+        );
+        assert_eq!(c.format(Some("{ranges}")).unwrap(), "1001-1021x10, 1041");
+
+        let c = Collection::new(
+            "head.".to_string(),
+            ".tail".to_string(),
+            4,
+            vec![1001, 1005],
+        );
+        assert_eq!(c.format(Some("{ranges}")).unwrap(), "1001, 1005");
+
+        // Two frames are too few to find a step from.
+        let c = Collection::new(
+            "head.".to_string(),
+            ".tail".to_string(),
+            4,
+            vec![1001, 1500],
+        );
+        assert_eq!(c.step(), 1);
+        assert!(!c.is_contiguous());
+        assert_eq!(c.holes().len(), 498);
+        assert_eq!(
+            c.format(Some("{range} [{ranges}]")).unwrap(),
+            "1001-1500 [1001, 1500]"
+        );
+        assert_eq!(c.separate().len(), 2);
+    }
+
+    #[test]
+    fn test_with_frames() {
+        let template = Collection::new("render.".to_string(), ".exr".to_string(), 4, vec![]);
+        let frames: FrameSet = "1001-1005,1010-1020x5".parse().unwrap();
+        let expected = template.with_frames(&frames).unwrap();
+        assert_eq!(
+            expected.format::<&str>(None).unwrap(),
+            "render.%04d.exr [1001-1005, 1010, 1015, 1020]"
+        );
+        assert_eq!(FrameSet::from(expected.indexes()), frames);
+        assert!(template
+            .with_frames(&"9999-10000".parse().unwrap())
+            .is_err());
+
+        let template = Collection::new("sim.".to_string(), ".bgeo".to_string(), 4, vec![])
+            .with_kind(IndexKind::Subframe { padding: 2 });
+        let c = template.with_frames(&"1001-1002".parse().unwrap()).unwrap();
+        assert_eq!(c.indexes(), &[100100, 100200]);
+    }
+
+    #[test]
+    fn test_huge_sequence() {
+        let template = Collection::new("cam.".to_string(), ".jpg".to_string(), 10, vec![]);
+        let frames: FrameSet = "1-40000000,40000002-90000000".parse().unwrap();
+        let c = template.with_frames(&frames).unwrap();
+        assert_eq!(c.len(), 89_999_999);
+        assert!(!c.is_contiguous());
+        assert_eq!(c.holes().indexes(), &[40000001]);
+        assert_eq!(c.separate().len(), 2);
+        assert!(c.contains("cam.0050000000.jpg"));
+        assert!(!c.contains("cam.0040000001.jpg"));
+        assert_eq!(
+            c.format(Some("{ranges}")).unwrap(),
+            "1-40000000, 40000002-90000000"
+        );
+
+        let c = template
+            .with_frames(&"1-2,1000000000".parse().unwrap())
+            .unwrap();
+        assert_eq!(c.holes().len(), 999_999_997);
+        assert_eq!(c.holes().into_iter().next().unwrap(), "cam.0000000003.jpg");
+
+        let c = Collection::new(
+            "cam.".to_string(),
+            ".jpg".to_string(),
+            10,
+            vec![1, 3, 20000001],
+        );
+        assert_eq!(c.step(), 2);
+        assert_eq!(c.holes().indexes.runs(), &[(5, 19999999, 2)]);
+
+        let c: Collection = "cam.%010d.jpg [1-20000000, 30000000-90000000]"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            c.indexes.runs(),
+            &[(1, 20000000, 1), (30000000, 90000000, 1)]
+        );
+        let c = crate::parse(
+            "cam.%010d.jpg [1-90000000] [1001-1010, 20000000-30000000]",
+            Some("{head}{padding}{tail} [{range}] [{holes}]"),
+        )
+        .unwrap();
+        assert_eq!(
+            c.indexes.runs(),
+            &[(1, 1000, 1), (1011, 19999999, 1), (30000001, 90000000, 1)]
+        );
+
+        let c: Collection = "r.%d.exr [1-1000000000000x2]".parse().unwrap();
+        assert_eq!(c.indexes.runs(), &[(1, 999999999999, 2)]);
+        assert_eq!(c.len(), 500_000_000_000);
+        assert_eq!(c.step(), 2);
+        assert!(c.is_contiguous());
+        assert!(c.contains("r.999999999999.exr"));
+        assert!(!c.contains("r.999999999998.exr"));
+        assert!(c.holes().is_empty());
+        assert_eq!(c.separate(), vec![c.clone()]);
+        assert_eq!(
+            c.format(None::<&str>).unwrap(),
+            "r.%d.exr [1-999999999999x2]"
+        );
+        let c = crate::parse(
+            "r.%d.exr [1-1000000000000x2] [5, 999999999991-999999999995]",
+            Some("{head}{padding}{tail} [{range}] [{holes}]"),
+        )
+        .unwrap();
+        assert_eq!(
+            c.indexes.runs(),
+            &[
+                (1, 1, 1),
+                (3, 3, 1),
+                (7, 999999999989, 2),
+                (999999999997, 999999999999, 2)
+            ]
+        );
+        assert_eq!(c.len(), 499_999_999_996);
+        assert!(!c.is_contiguous());
+        assert_eq!(
+            c.holes().indexes.runs(),
+            &[(5, 5, 1), (999999999991, 999999999995, 2)]
+        );
     }
 
     #[test]
@@ -491,6 +1496,89 @@ mod tests {
         assert_ne!(c1, d1);
     }
 
+    #[test]
+    fn test_ord_and_hash() {
+        let collection = |head: &str, padding: i32, indexes: Vec<i64>| {
+            Collection::new(head.to_string(), ".exr".to_string(), padding, indexes)
+        };
+        let mut collections = [
+            collection("b.", 4, vec![1, 2]),
+            collection("a.", 4, vec![5, 6]),
+            collection("a.", 4, vec![1, 3]),
+            collection("a.", 4, vec![1, 2]),
+            collection("a.", 2, vec![9]),
+        ];
+        collections.sort();
+        let formatted: Vec<String> = collections.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            formatted,
+            vec![
+                "a.%02d.exr [9]",
+                "a.%04d.exr [1-2]",
+                "a.%04d.exr [1, 3]",
+                "a.%04d.exr [5-6]",
+                "b.%04d.exr [1-2]",
+            ]
+        );
+
+        // Kinds are compared before indexes, in the natural order too.
+        let mut collections = [
+            collection("a.", 4, vec![1001]).with_kind(IndexKind::Udim),
+            collection("a.", 4, vec![100100]).with_kind(IndexKind::Subframe { padding: 2 }),
+            collection("a.", 4, vec![1002, 1003]),
+        ];
+        let kinds = |collections: &[Collection]| -> Vec<IndexKind> {
+            collections.iter().map(|c| c.kind()).collect()
+        };
+        let expected = vec![
+            IndexKind::Frame,
+            IndexKind::Subframe { padding: 2 },
+            IndexKind::Udim,
+        ];
+        collections.sort();
+        assert_eq!(kinds(&collections), expected);
+        collections.reverse();
+        collections.sort_by(crate::natural_collection_cmp);
+        assert_eq!(kinds(&collections), expected);
+
+        let collections = [
+            collection("a.", 4, vec![1, 2]),
+            collection("a.", 4, vec![1, 3]),
+            collection("b.", 4, vec![1, 2]),
+        ];
+        let mut set = std::collections::HashSet::new();
+        set.insert(collections[0].clone());
+        assert!(set.contains(&collection("a.", 4, vec![2, 1])));
+        assert!(!set.contains(&collections[1]));
+        let mut map = std::collections::BTreeMap::new();
+        map.insert(collections[2].clone(), "b");
+        assert_eq!(map.get(&collection("b.", 4, vec![1, 2])), Some(&"b"));
+    }
+
+    #[test]
+    fn test_display_from_str() {
+        let c: Collection = "render.%04d.exr [1-10]".parse().unwrap();
+        assert_eq!(c.indexes(), (1..=10).collect::<Vec<i64>>());
+        assert_eq!(c.to_string(), "render.%04d.exr [1-10]");
+
+        for value in [
+            "render.%04d.exr [1001-1003, 1005]",
+            "render.%04d.exr [1010-1020x5]",
+            "sim.%04d.%02d.bgeo [1001-1001.75 @0.25]",
+            "diffuse.<UDIM>.tx [1001-1002, 1011]",
+            "cam.%d.jpg [-5--1, 7]",
+        ] {
+            let c: Collection = value.parse().unwrap();
+            assert_eq!(c.to_string(), value);
+            assert_eq!(c.to_string().parse::<Collection>().unwrap(), c);
+        }
+
+        let empty = Collection::new("a.".to_string(), ".exr".to_string(), 4, vec![]);
+        assert_eq!(empty.to_string(), "a.%04d.exr []");
+        assert_eq!(empty.to_string().parse::<Collection>().unwrap(), empty);
+        assert!("render.exr".parse::<Collection>().is_err());
+    }
+
     #[test]
     fn test_iterator() {
         let c1 = Collection::new(
@@ -526,4 +1614,33 @@ mod tests {
         assert_eq!(iter.next(), Some("head.00023.tail".to_string()));
         assert_eq!(iter.next(), None);
     }
+
+    #[test]
+    fn test_negative_indexes() {
+        let c = Collection::new(
+            "sim.".to_string(),
+            ".bgeo".to_string(),
+            3,
+            vec![-12, -10, -1, 0, 2],
+        );
+        let members: Vec<String> = (&c).into_iter().collect();
+        assert_eq!(
+            members,
+            vec![
+                "sim.-012.bgeo",
+                "sim.-010.bgeo",
+                "sim.-001.bgeo",
+                "sim.000.bgeo",
+                "sim.002.bgeo",
+            ]
+        );
+        assert_eq!(
+            c.holes().indexes(),
+            vec![-11, -9, -8, -7, -6, -5, -4, -3, -2, 1]
+        );
+        assert_eq!(c.separate().len(), 4);
+        assert_eq!(c.format(Some("{ranges}")).unwrap(), "-12, -10, -1-0, 2");
+        assert!(c.match_item("sim.-011.bgeo").unwrap().is_some());
+        assert!(c.match_item("sim.-11.bgeo").unwrap().is_none());
+    }
 }
@@ -0,0 +1,107 @@
+use std::fmt;
+
+use crate::{IndexKind, MatchSelection, PaddingStyle};
+
+// Errors returned when assembling, formatting, parsing or matching collections.
+#[derive(Debug)]
+pub enum Error {
+    // A pattern could not be compiled as a regular expression.
+    InvalidPattern(String, regex::Error),
+    // A pattern does not contain a required named group, such as "index".
+    MissingGroup {
+        pattern: String,
+        group: String,
+    },
+    // A format string contains a key that is not supported.
+    UnknownFormatKey(String),
+    // A format string could not be applied.
+    InvalidFormat(String, String),
+    // A value does not match the format it is being parsed with.
+    ParseFailed {
+        value: String,
+        pattern: String,
+    },
+    // The operation requires a collection with at least one index.
+    EmptyCollection,
+    // An index has more digits than the padding of its collection.
+    IndexExceedsPadding {
+        index: String,
+        padding: i32,
+    },
+    // An item does not have the head, tail and padding of a collection.
+    IncompatibleItem(String),
+    // An item is not in the collection it is being removed from.
+    MissingItem(String),
+    // A collection does not have the head, tail, padding and kind of index
+    // of the collection it is being merged into.
+    IncompatibleCollection {
+        head: String,
+        tail: String,
+        padding: i32,
+        kind: IndexKind,
+    },
+    // A value is not a valid frame range, such as "1001-1050,1060".
+    InvalidFrameRange(String),
+    // A padding style cannot write the indexes of a collection, such as RV
+    // padding for subframes.
+    UnsupportedPaddingStyle(PaddingStyle),
+    // A match selection is not supported, such as MostVarying by
+    // assemble_multi.
+    UnsupportedSelection(MatchSelection),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidPattern(pattern, error) => {
+                write!(f, "invalid pattern \"{}\": {}", pattern, error)
+            }
+            Error::MissingGroup { pattern, group } => write!(
+                f,
+                "pattern \"{}\" has no named group \"{}\"",
+                pattern, group
+            ),
+            Error::UnknownFormatKey(key) => write!(f, "unknown format key \"{}\"", key),
+            Error::InvalidFormat(format, reason) => {
+                write!(f, "invalid format \"{}\": {}", format, reason)
+            }
+            Error::ParseFailed { value, pattern } => {
+                write!(f, "\"{}\" does not match format \"{}\"", value, pattern)
+            }
+            Error::EmptyCollection => write!(f, "collection has no indexes"),
+            Error::IndexExceedsPadding { index, padding } => {
+                write!(f, "index {} is wider than padding {}", index, padding)
+            }
+            Error::IncompatibleItem(item) => {
+                write!(f, "\"{}\" does not match the collection", item)
+            }
+            Error::MissingItem(item) => write!(f, "\"{}\" is not in the collection", item),
+            Error::IncompatibleCollection {
+                head,
+                tail,
+                padding,
+                kind,
+            } => write!(
+                f,
+                "collection with head \"{}\", tail \"{}\", padding {} and {:?} indexes is not compatible",
+                head, tail, padding, kind
+            ),
+            Error::InvalidFrameRange(value) => write!(f, "invalid frame range \"{}\"", value),
+            Error::UnsupportedPaddingStyle(style) => {
+                write!(f, "{} padding cannot be written for subframes", style.key())
+            }
+            Error::UnsupportedSelection(selection) => {
+                write!(f, "{:?} match selection is not supported", selection)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InvalidPattern(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
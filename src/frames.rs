@@ -0,0 +1,282 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::ranges::RangeSet;
+use crate::Error;
+
+// Frames from start to end inclusive, step apart, such as 1100-1200x5. The
+// end is always a frame of the range, and the step is always positive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FrameRange {
+    start: i64,
+    end: i64,
+    step: i64,
+}
+
+// A set of frames, written as comma separated frames and ranges with an
+// optional step, such as "1001-1050,1060,1100-1200x5" or "-10--5". Frames
+// are kept as ranges of consecutive frames, so long ranges take little
+// space, and formatted as those ranges with single frames a constant step
+// apart joined into stepped ranges.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct FrameSet {
+    pub(crate) frames: RangeSet,
+}
+
+impl FrameRange {
+    // Return the range of frames from start to end, step apart, with end
+    // moved back to the last frame of the range. Returns an error if end is
+    // before start or step is not positive.
+    pub fn new(start: i64, end: i64, step: i64) -> Result<FrameRange, Error> {
+        if end < start || step <= 0 {
+            return Err(Error::InvalidFrameRange(format!(
+                "{}-{}x{}",
+                start, end, step
+            )));
+        }
+        // The difference between start and end may not fit in an i64.
+        let (start_wide, step_wide) = (start as i128, step as i128);
+        let end = start_wide + (end as i128 - start_wide) / step_wide * step_wide;
+        Ok(FrameRange {
+            start,
+            end: end as i64,
+            step,
+        })
+    }
+
+    pub fn start(&self) -> i64 {
+        self.start
+    }
+
+    pub fn end(&self) -> i64 {
+        self.end
+    }
+
+    pub fn step(&self) -> i64 {
+        self.step
+    }
+
+    // Return the frames of the range in ascending order.
+    pub fn frames(&self) -> impl Iterator<Item = i64> {
+        (self.start..=self.end).step_by(self.step as usize)
+    }
+}
+
+impl FromStr for FrameRange {
+    type Err = Error;
+
+    // Parse a frame, such as "1001", or range, such as "1001-1050" or
+    // "1100-1200x5".
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidFrameRange(value.to_string());
+        let (start, end, step) = split_range(value).ok_or_else(invalid)?;
+        let step = match step {
+            Some(step) => step.parse::<i64>().map_err(|_| invalid())?,
+            None => 1,
+        };
+        let start = start.parse::<i64>().map_err(|_| invalid())?;
+        let end = end.parse::<i64>().map_err(|_| invalid())?;
+        FrameRange::new(start, end, step).map_err(|_| invalid())
+    }
+}
+
+// Split a frame, such as "1001", or range, such as "1001-1050" or
+// "1100-1200x5", into its start, end and step. The separator is the first
+// dash that is not the sign of the start. Returns None for a step without an
+// end, such as "5x2".
+pub(crate) fn split_range(range: &str) -> Option<(&str, &str, Option<&str>)> {
+    let (range, step) = match range.trim().split_once('x') {
+        Some((range, step)) => (range, Some(step)),
+        None => (range.trim(), None),
+    };
+    match range.char_indices().skip(1).find(|(_, c)| *c == '-') {
+        Some((separator, _)) => Some((&range[..separator], &range[separator + 1..], step)),
+        None if step.is_none() => Some((range, range, None)),
+        None => None,
+    }
+}
+
+impl fmt::Display for FrameRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.start == self.end, self.step) {
+            (true, _) => write!(f, "{}", self.start),
+            (false, 1) => write!(f, "{}-{}", self.start, self.end),
+            (false, step) => write!(f, "{}-{}x{}", self.start, self.end, step),
+        }
+    }
+}
+
+impl FrameSet {
+    pub fn new() -> FrameSet {
+        FrameSet::default()
+    }
+
+    // Return the frames in ascending order.
+    pub fn frames(&self) -> Vec<i64> {
+        self.frames.to_vec()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn contains(&self, frame: i64) -> bool {
+        self.frames.contains(frame)
+    }
+
+    // Return the set of the frames of ranges. Ranges are stored without
+    // listing their frames.
+    fn from_ranges(ranges: &[FrameRange]) -> FrameSet {
+        FrameSet {
+            frames: RangeSet::from_runs(
+                ranges
+                    .iter()
+                    .map(|range| (range.start, range.end, range.step)),
+            ),
+        }
+    }
+
+    // Return the frames as ranges in ascending order. Consecutive frames
+    // form a range, and runs of at least three single frames a constant step
+    // apart form a stepped range.
+    pub fn ranges(&self) -> Vec<FrameRange> {
+        let mut ranges = vec![];
+        for &(start, end, step) in self.frames.runs() {
+            // A stepped run of two frames is two single frames.
+            if step != 1 && end - start == step {
+                for frame in [start, end] {
+                    ranges.push(FrameRange {
+                        start: frame,
+                        end: frame,
+                        step: 1,
+                    });
+                }
+            } else {
+                ranges.push(FrameRange { start, end, step });
+            }
+        }
+        ranges
+    }
+}
+
+impl From<Vec<i64>> for FrameSet {
+    fn from(frames: Vec<i64>) -> FrameSet {
+        FrameSet {
+            frames: RangeSet::from_values(frames),
+        }
+    }
+}
+
+impl From<&[i64]> for FrameSet {
+    fn from(frames: &[i64]) -> FrameSet {
+        FrameSet::from(frames.to_vec())
+    }
+}
+
+impl From<FrameRange> for FrameSet {
+    fn from(range: FrameRange) -> FrameSet {
+        FrameSet::from_ranges(&[range])
+    }
+}
+
+impl From<FrameSet> for Vec<i64> {
+    fn from(frames: FrameSet) -> Vec<i64> {
+        frames.frames.to_vec()
+    }
+}
+
+impl FromStr for FrameSet {
+    type Err = Error;
+
+    // Parse comma separated frames and ranges, such as
+    // "1001-1050,1060,1100-1200x5". An empty string is an empty set.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.trim().is_empty() {
+            return Ok(FrameSet::new());
+        }
+        let ranges = value
+            .split(',')
+            .map(|range| range.parse::<FrameRange>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| Error::InvalidFrameRange(value.to_string()))?;
+        Ok(FrameSet::from_ranges(&ranges))
+    }
+}
+
+impl fmt::Display for FrameSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ranges: Vec<String> = self.ranges().iter().map(|r| r.to_string()).collect();
+        write!(f, "{}", ranges.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let frames: FrameSet = "1001-1050,1060,1100-1200x5".parse().unwrap();
+        assert_eq!(frames.len(), 50 + 1 + 21);
+        assert!(frames.contains(1105));
+        assert!(!frames.contains(1106));
+        assert_eq!(frames.to_string(), "1001-1050,1060,1100-1200x5");
+
+        let frames: FrameSet = " -10--8, 1-3x2 ,-1-1".parse().unwrap();
+        assert_eq!(frames.frames(), &[-10, -9, -8, -1, 0, 1, 3]);
+        assert_eq!(frames.to_string(), "-10--8,-1-1,3");
+
+        let frames: FrameSet = "1-10x4, 5".parse().unwrap();
+        assert_eq!(frames.frames(), &[1, 5, 9]);
+        assert_eq!(frames.to_string(), "1-9x4");
+
+        assert!("".parse::<FrameSet>().unwrap().is_empty());
+        for value in ["1001-", "10-1", "1-10x0", "5x2", "1,,2", "a"] {
+            assert!(matches!(
+                value.parse::<FrameSet>(),
+                Err(Error::InvalidFrameRange(v)) if v == value
+            ));
+        }
+    }
+
+    #[test]
+    fn test_ranges() {
+        let frames = FrameSet::from(vec![7, 1, 2, 3, 5, 2, 9, 11, 12]);
+        assert_eq!(frames.frames(), &[1, 2, 3, 5, 7, 9, 11, 12]);
+        assert_eq!(frames.to_string(), "1-3,5-9x2,11-12");
+
+        let frames = FrameSet::from(vec![1, 2, 10, 20]);
+        assert_eq!(frames.to_string(), "1-2,10,20");
+        assert_eq!(FrameSet::from(vec![1, 3, 10, 17]).to_string(), "1,3-17x7");
+        assert_eq!(Vec::from(frames), vec![1, 2, 10, 20]);
+
+        let range: FrameRange = "1100-1203x5".parse().unwrap();
+        assert_eq!(range, FrameRange::new(1100, 1200, 5).unwrap());
+        assert_eq!(FrameSet::from(range).len(), 21);
+        assert_eq!((range.start(), range.end(), range.step()), (1100, 1200, 5));
+
+        let range = FrameRange::new(i64::MIN, i64::MAX, 1).unwrap();
+        assert_eq!((range.start(), range.end()), (i64::MIN, i64::MAX));
+        assert_eq!(range.to_string().parse::<FrameRange>().unwrap(), range);
+        let range = FrameRange::new(i64::MIN, i64::MAX, i64::MAX).unwrap();
+        assert_eq!(range.end(), i64::MAX - 1);
+        assert_eq!(range.frames().count(), 3);
+
+        let frames: FrameSet = "1-1000000000000x2,1000000000003".parse().unwrap();
+        assert_eq!(frames.len(), 500_000_000_001);
+        assert!(frames.contains(999_999_999_999) && !frames.contains(1_000_000_000_000));
+        assert_eq!(frames.to_string(), "1-999999999999x2,1000000000003");
+
+        for (start, end, step) in [(1, 5, 0), (1, 5, -1), (5, 1, 1)] {
+            assert!(matches!(
+                FrameRange::new(start, end, step),
+                Err(Error::InvalidFrameRange(v)) if v == format!("{}-{}x{}", start, end, step)
+            ));
+        }
+    }
+}
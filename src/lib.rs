@@ -1,182 +1,351 @@
-use lazy_static::lazy_static;
-use regex::Regex;
-use std::collections::HashMap;
+use ranges::RangeSet;
+use regex::{Regex, RegexBuilder};
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+mod assembler;
 mod collection;
-use collection::Collection;
+mod error;
+mod frames;
+mod matcher;
+mod multi;
+mod natural;
+mod options;
+mod os;
+mod padding;
+mod pattern;
+mod ranges;
+mod remainder;
+#[cfg(feature = "serde")]
+mod serialize;
+mod subframe;
+mod udim;
+// Run the library example in the README as a doctest.
+#[cfg(doctest)]
+#[doc = include_str!("../README.md")]
+struct ReadmeDoctests;
 
-static DIGITS_PATTERN: &str = "(?P<index>(?P<padding>0*)\\d+)";
-static FRAME_PATTERN: &str = "\\.(?P<index>(?P<padding>0*)\\d+)\\.\\D+\\d?$";
+pub use assembler::Assembler;
+pub use collection::{Collection, IndexKind};
+pub use error::Error;
+pub use frames::{FrameRange, FrameSet};
+pub use matcher::CollectionMatcher;
+pub use multi::{assemble_multi, MultiCollection};
+pub use options::{AssembleOptions, CollectionOrder, MatchSelection};
+pub use padding::PaddingStyle;
+pub use pattern::Pattern;
+pub use remainder::{Remainder, RemainderReason};
+pub use subframe::Subframe;
+pub use udim::UdimTile;
+
+static DEFAULT_FORMAT: &str = "{head}{padding}{tail} [{ranges}]";
+static NUMBER_PATTERN: &str = "-?\\d+(?:\\.\\d+)?";
+static RANGES_PATTERN: &str =
+    "-?\\d+(?:\\.\\d+)?(?:--?\\d+(?:\\.\\d+)?(?:x\\d+)?)?(?:, -?\\d+(?:\\.\\d+)?(?:--?\\d+(?:\\.\\d+)?(?:x\\d+)?)?)*(?: @\\d+(?:\\.\\d+)?)?";
+static DIGITS_PATTERN: &str = "(?:(?:^|[._])(?P<sign>-))?(?P<index>(?P<padding>0*)\\d+)";
+static SUBFRAME_PATTERN: &str =
+    "\\.(?P<sign>-)?(?P<index>(?P<padding>0*)\\d+)\\.(?P<subframe>\\d+)(?:\\.[^./\\d][^./]*)*$";
+static FRAME_PATTERN: &str = "\\.(?P<index>-?(?P<padding>0*)\\d+)\\.\\D+\\d?$";
 static VERSION_PATTERN: &str = "v(?P<index>(?P<padding>0*)\\d+)";
+static UDIM_PATTERN: &str = "(?:^|\\D)(?P<index>1\\d{3})(?:\\D|$)";
+static HOUDINI_PATTERN: &str = "[._](?P<index>-?(?P<padding>0*)\\d+)(?:\\.[A-Za-z]\\w*)+$";
+static BLENDER_PATTERN: &str = "(?P<index>(?P<padding>0*)\\d+)\\.[A-Za-z]\\w*$";
+
+// Head, tail, padding and index kind that items are grouped into collections
+// by.
+type CollectionKey = (String, String, i32, IndexKind);
 
+// Group items into collections by the numeric component matched by patterns,
+// using the default AssembleOptions. Returns the collections and the items
+// that do not belong to any collection, or an error if a pattern is invalid or
+// has no "index" group. Collections are returned in natural order of head,
+// tail and padding, then first index, and the remainder in natural order.
 pub fn assemble<T: AsRef<str>>(
-    iterable: &Vec<T>,
+    iterable: &[T],
     patterns: Option<Vec<String>>,
-) -> (Vec<Collection>, Vec<String>) {
-    let mut compiled_patterns: Vec<Regex> = vec![];
-    let mut collection_map: HashMap<(String, String, i32), Vec<i32>> = HashMap::new();
-    let mut remainder: Vec<String> = Vec::new();
-
-    match patterns {
-        Some(patterns) => {
-            for pattern in patterns.iter() {
-                compiled_patterns.push(Regex::new(pattern).unwrap());
-            }
-        }
-        None => {
-            lazy_static! {
-                static ref DIGITS_REGEX: Regex = Regex::new(&*DIGITS_PATTERN).unwrap();
-            }
-            compiled_patterns.push(DIGITS_REGEX.to_owned());
-        }
-    }
+) -> Result<(Vec<Collection>, Vec<String>), Error> {
+    assemble_with(iterable, patterns, &AssembleOptions::default())
+}
 
-    for item in iterable.iter() {
-        let mut matched = false;
-
-        for pattern in compiled_patterns.iter() {
-            for captures in pattern.captures_iter(item.as_ref()) {
-                let index_match = captures.name("index").unwrap();
-                let head = &item.as_ref()[..index_match.start()];
-                let tail = &item.as_ref()[index_match.end()..];
-
-                let padding = match captures.name("padding") {
-                    Some(_) => index_match.range().count() as i32,
-                    None => 0,
-                };
-
-                let key = (head.to_string(), tail.to_string(), padding);
-                let index = match index_match.as_str().parse::<i32>() {
-                    Ok(i) => i,
-                    Err(_) => continue,
-                };
-                if collection_map.contains_key(&key) {
-                    collection_map.get_mut(&key).unwrap().push(index)
-                } else {
-                    collection_map.insert(key, vec![index]);
-                }
-                matched = true;
-            }
-        }
-        if !matched {
-            remainder.push(item.as_ref().to_string());
-        }
-    }
+// Group items into collections as assemble does, configured by options.
+pub fn assemble_with<T: AsRef<str>>(
+    iterable: &[T],
+    patterns: Option<Vec<String>>,
+    options: &AssembleOptions,
+) -> Result<(Vec<Collection>, Vec<String>), Error> {
+    let mut assembler = Assembler::new(patterns, options)?;
+    assembler.extend(iterable);
+    Ok(assembler.finish())
+}
 
-    // sort the indexes in the collection map
-    for (_k, v) in collection_map.iter_mut() {
-        v.sort();
-    }
+// Group items into collections as assemble_with does, with the reason each
+// item of the remainder is not in a collection.
+pub fn assemble_with_reasons<T: AsRef<str>>(
+    iterable: &[T],
+    patterns: Option<Vec<String>>,
+    options: &AssembleOptions,
+) -> Result<(Vec<Collection>, Vec<Remainder>), Error> {
+    let mut assembler = Assembler::new(patterns, options)?;
+    assembler.extend(iterable);
+    Ok(assembler.finish_with_reasons())
+}
 
-    // form collections
-    let mut collections = Vec::new();
-    let mut merge_candidates = Vec::new();
-    for ((head, tail, padding), indexes) in collection_map.iter() {
-        collections.push(Collection::new(
-            head.to_string(),
-            tail.to_string(),
-            *padding,
-            indexes.to_vec(),
-        ));
+// Group paths into collections as assemble_with does. Names that are not
+// valid UTF-8 are kept exactly: bytes that are not part of a valid character
+// appear in the head and tail of collections as private use characters from
+// U+10FF00, and Collection::paths returns the original paths.
+pub fn assemble_paths<P: AsRef<Path>>(
+    paths: &[P],
+    patterns: Option<Vec<String>>,
+    options: &AssembleOptions,
+) -> Result<(Vec<Collection>, Vec<PathBuf>), Error> {
+    let mut assembler = Assembler::new(patterns, options)?;
+    assembler.extend(
+        paths
+            .iter()
+            .map(|path| os::encode(path.as_ref().as_os_str())),
+    );
+    let (collections, remainder) = assembler.finish();
+    let remainder = remainder
+        .iter()
+        .map(|item| PathBuf::from(os::decode(item)))
+        .collect();
+    Ok((collections, remainder))
+}
 
-        if *padding == 0 {
-            merge_candidates.push(Collection::new(
-                head.to_string(),
-                tail.to_string(),
-                *padding,
-                indexes.to_vec(),
-            ));
+// Compile patterns, returning an error if a pattern is invalid or does not
+// have the group.
+fn compile_patterns(
+    patterns: &[String],
+    group: &str,
+    options: &AssembleOptions,
+) -> Result<Vec<Regex>, Error> {
+    let mut compiled_patterns = vec![];
+    for pattern in patterns.iter() {
+        let compiled_pattern = RegexBuilder::new(pattern)
+            .case_insensitive(!options.case_sensitive)
+            .build()
+            .map_err(|error| Error::InvalidPattern(pattern.to_string(), error))?;
+        if !compiled_pattern
+            .capture_names()
+            .any(|name| name == Some(group))
+        {
+            return Err(Error::MissingGroup {
+                pattern: pattern.to_string(),
+                group: group.to_string(),
+            });
         }
+        compiled_patterns.push(compiled_pattern);
     }
+    Ok(compiled_patterns)
+}
 
-    // Merge together collections that align on padding boundaries. For example,
-    // 0998-0999 and 1000-1001 can be merged into 0998-1001. Note that only
-    // indexes within the padding width limit are merged. If a collection is
-    // entirely merged into another then it will not be included as a separate
-    // collection in the results.
-    let mut fully_merged = vec![];
-    for collection in collections.iter_mut() {
-        if collection.padding == 0 {
-            continue;
-        }
+// Compare collections in natural order of head, tail and padding, then by
+// their kind and first index, as Collection's Ord does.
+fn natural_collection_cmp(a: &Collection, b: &Collection) -> Ordering {
+    natural::natural_cmp(&a.head, &b.head)
+        .then_with(|| natural::natural_cmp(&a.tail, &b.tail))
+        .then_with(|| a.padding.cmp(&b.padding))
+        .then_with(|| a.kind.cmp(&b.kind))
+        .then_with(|| a.indexes.first().cmp(&b.indexes.first()))
+}
 
-        for candidate in merge_candidates.iter() {
-            if candidate.head == collection.head && candidate.tail == collection.tail {
-                let mut merged_index_count = 0;
-                for index in candidate.indexes.iter() {
-                    if index.to_string().len() as i32 == collection.padding {
-                        collection.indexes.push(*index);
-                        merged_index_count += 1;
-                    }
-                }
+// Parse a formatted collection string back into a Collection. The pattern
+// uses the same keys as Collection::format and defaults to the default format
+// "{head}{padding}{tail} [{ranges}]". Returns an error if value does not match
+// the pattern, the pattern contains an unknown key or an index is wider than
+// the padding.
+pub fn parse<T: AsRef<str>>(value: &str, pattern: Option<T>) -> Result<Collection, Error> {
+    let pattern = match &pattern {
+        Some(pattern) => pattern.as_ref(),
+        None => DEFAULT_FORMAT,
+    };
+    let parse_failed = || Error::ParseFailed {
+        value: value.to_string(),
+        pattern: pattern.to_string(),
+    };
 
-                if merged_index_count == candidate.indexes.len() {
-                    fully_merged.push(candidate);
-                }
-            }
+    // Padding keys are tried in each style they accept in turn, such as
+    // printf and then hashes for {padding}, until one matches.
+    let styles = format_keys(pattern)?
+        .into_iter()
+        .find_map(PaddingStyle::parse_styles)
+        .unwrap_or(&[PaddingStyle::Printf]);
+    let mut matched = None;
+    for style in styles {
+        let regex_str = parse_regex(pattern, *style)?;
+        let regex = Regex::new(&regex_str)
+            .map_err(|error| Error::InvalidPattern(regex_str.to_string(), error))?;
+        if let Some(captures) = regex.captures(value) {
+            matched = Some((captures, style));
+            break;
         }
     }
+    let (captures, style) = matched.ok_or_else(parse_failed)?;
+    let group = |name: &str| captures.name(name).map(|m| m.as_str()).unwrap_or("");
 
-    // filter out fully merged collections.
-    let collections: Vec<Collection> = collections
-        .into_iter()
-        .filter(|x| !fully_merged.contains(&x))
-        .collect();
+    let token = style.parse_token(&captures).ok_or_else(parse_failed)?;
+    let is_udim = ["udim", "udim_token", "uvtile_token"]
+        .iter()
+        .any(|name| captures.name(name).is_some());
+    let padding = match is_udim {
+        true => 4,
+        false => token.padding,
+    };
 
-    // Filter out collections that do not have at least as many indexes as
-    // minimum_items. In addition, add any members of a filtered collection,
-    // which are not members of an unfiltered collection, to the remainder.
-    let minimum_items = 2;
-    let mut filtered = vec![];
-    let mut remainder_candidates = vec![];
-    for collection in collections.into_iter() {
-        if collection.indexes.len() > minimum_items {
-            filtered.push(collection);
-        } else {
-            for member in collection.into_iter() {
-                remainder_candidates.push(member);
-            }
+    // Subframe precision is given by the padding, or otherwise by the most
+    // fractional digits of any index.
+    let precision = match token.subframe {
+        None => ["ranges", "range", "holes", "start", "end", "step"]
+            .iter()
+            .flat_map(|name| group(name).split([',', ' ', '-', '@']))
+            .map(subframe::decimal_precision)
+            .max()
+            .unwrap_or(0),
+        Some(subframe) => subframe,
+    };
+
+    let mut indexes = RangeSet::new();
+    if captures.name("ranges").is_some() {
+        indexes = parse_ranges(group("ranges"), precision).ok_or_else(parse_failed)?;
+    } else if captures.name("range").is_some() {
+        indexes = parse_ranges(group("range"), precision).ok_or_else(parse_failed)?;
+    } else if captures.name("start").is_some() && captures.name("end").is_some() {
+        let start = subframe::parse_decimal(group("start"), precision).ok_or_else(parse_failed)?;
+        let end = subframe::parse_decimal(group("end"), precision).ok_or_else(parse_failed)?;
+        let step = match group("step") {
+            "" => subframe::scale(precision),
+            step => subframe::parse_decimal(step, precision).ok_or_else(parse_failed)?,
+        };
+        if step <= 0 {
+            return Err(parse_failed());
         }
+        indexes = RangeSet::from_runs([(start, end, step)]);
+    } else if let Some(frames) = token.frames {
+        indexes = FrameSet::from(frames).frames;
     }
 
-    for candidate in remainder_candidates.into_iter() {
-        // Check if candidate has already been added to remainder to avoid
-        // duplicate entries.
-        if remainder.contains(&candidate) {
-            continue;
-        }
+    if captures.name("holes").is_some() {
+        let holes = parse_ranges(group("holes"), precision).ok_or_else(parse_failed)?;
+        indexes = indexes.combine(&holes, |index, hole| index && !hole);
+    }
 
-        let mut has_membership = false;
+    let kind = match (precision, is_udim) {
+        (0, true) => IndexKind::Udim,
+        (0, false) => IndexKind::Frame,
+        (precision, _) => IndexKind::Subframe { padding: precision },
+    };
+    // Wider indexes are written in full with padding 1, as they are when
+    // unpadded, so a token for one digit is unpadded if any index is wider.
+    let wide = [indexes.first(), indexes.last()]
+        .iter()
+        .flatten()
+        .any(|index| collection::index_width(kind.whole(*index)) > 1);
+    let padding = match padding {
+        1 if wide => 0,
+        padding => padding,
+    };
+    let mut collection = Collection::new(
+        group("head").to_string(),
+        group("tail").to_string(),
+        padding,
+        vec![],
+    );
+    collection.indexes = indexes;
+    collection.try_with_kind(kind)
+}
 
-        for collection in filtered.iter() {
-            if collection.contains(&candidate) {
-                has_membership = true;
-                break;
+// Return the keys of a format pattern in order. Returns an error if a key is
+// not closed.
+fn format_keys(pattern: &str) -> Result<Vec<&str>, Error> {
+    let mut keys = vec![];
+    let mut remaining = pattern;
+    while let Some(open) = remaining.find('{') {
+        let close = match remaining[open..].find('}') {
+            Some(close) => close + open,
+            None => {
+                return Err(Error::InvalidFormat(
+                    pattern.to_string(),
+                    "expected '}'".to_string(),
+                ))
             }
-        }
+        };
+        keys.push(&remaining[open + 1..close]);
+        remaining = &remaining[close + 1..];
+    }
+    Ok(keys)
+}
 
-        if !has_membership {
-            remainder.push(candidate.to_string());
-        }
+// Build a regex matching values formatted with pattern, escaping the literal
+// text between keys, with the first padding key matching tokens of style and
+// the tail matching text after the last of them. Later padding keys match
+// tokens of any style they accept, without groups, as only the first is read.
+fn parse_regex(pattern: &str, style: PaddingStyle) -> Result<String, Error> {
+    let mut regex_str = String::from("^");
+    let mut remaining = pattern;
+    let mut has_padding = false;
+    for key in format_keys(pattern)? {
+        let open = remaining.find('{').unwrap_or(0);
+        regex_str.push_str(&regex::escape(&remaining[..open]));
+        let expression = match key {
+            "head" => "(?P<head>.*?)".to_string(),
+            "tail" => format!("(?P<tail>{})", style.tail_pattern()),
+            "udim" => format!("(?P<udim_token>{})", udim::UDIM_TOKEN),
+            "uvtile" => format!("(?P<uvtile_token>{})", udim::UVTILE_TOKEN),
+            "start" => format!("(?P<start>{})", NUMBER_PATTERN),
+            "end" => format!("(?P<end>{})", NUMBER_PATTERN),
+            "step" => format!("(?P<step>{})", NUMBER_PATTERN),
+            "range" => format!("(?P<range>{})?", RANGES_PATTERN),
+            "ranges" => format!("(?P<ranges>{})?", RANGES_PATTERN),
+            "holes" => format!("(?P<holes>{})?", RANGES_PATTERN),
+            key => match PaddingStyle::parse_styles(key) {
+                Some(_) if !has_padding => {
+                    has_padding = true;
+                    style.pattern()
+                }
+                Some(styles) => {
+                    let patterns: Vec<String> =
+                        styles.iter().map(|style| style.unnamed_pattern()).collect();
+                    format!("(?:{})", patterns.join("|"))
+                }
+                None => return Err(Error::UnknownFormatKey(key.to_string())),
+            },
+        };
+        regex_str.push_str(&expression);
+        remaining = &remaining[open + key.len() + 2..];
+    }
+    regex_str.push_str(&regex::escape(remaining));
+    regex_str.push('$');
+    Ok(regex_str)
+}
+
+// Parse comma separated ranges, such as "1001-1003, 1005" or "-10--5", into
+// indexes. Ranges of frames may end with their step, such as "1001-1099x2".
+// Subframe ranges are followed by their step, such as "1001-1002 @0.25", and
+// are parsed into fixed point indexes with precision fractional digits.
+fn parse_ranges(ranges: &str, precision: i32) -> Option<RangeSet> {
+    let (ranges, step) = match ranges.split_once(" @") {
+        Some((ranges, step)) => (ranges, subframe::parse_decimal(step, precision)?),
+        None => (ranges, subframe::scale(precision)),
+    };
+    if step <= 0 {
+        return None;
     }
 
-    // // Set padding for all ambiguous collections according to the
-    // // assume_padded_when_ambiguous setting.
-    // let assume_padded_when_ambiguous = false;
-    // if assume_padded_when_ambiguous {
-    //     for collection in filtered.iter_mut() {
-    //         if collection.padding == 0 && collection.indexes.len() != 0 {
-    //             let start = collection.indexes[0].to_string();
-    //             let end = collection.indexes.last().unwrap().to_string();
-    //             let first_index_width = start.chars().count();
-    //             let last_index_width = end.chars().count();
-    //             if first_index_width == last_index_width {
-    //                 collection.padding = first_index_width as i32;
-    //             }
-    //         }
-    //     }
-    // }
-
-    return (filtered, remainder);
+    let mut indexes = vec![];
+    for part in ranges.split(',').filter(|x| !x.trim().is_empty()) {
+        let (start, end, frames) = frames::split_range(part)?;
+        let step = match frames {
+            Some(frames) => frames.parse::<i64>().ok()?.checked_mul(step)?,
+            None => step,
+        };
+        if step <= 0 {
+            return None;
+        }
+        let start = subframe::parse_decimal(start, precision)?;
+        let end = subframe::parse_decimal(end, precision)?;
+        indexes.push((start, end, step));
+    }
+    Some(RangeSet::from_runs(indexes))
 }
 
 #[cfg(test)]
@@ -191,12 +360,12 @@ mod tests {
             "shot/task/main_v001/render.1004.exr",
             "shot/task/main_v001/render.1005.exr",
         ];
-        let (collections, _remainder) = assemble(&files, None);
+        let (collections, _remainder) = assemble(&files, None).unwrap();
         assert_eq!(collections.len(), 1);
         assert_eq!(collections[0].head, "shot/task/main_v001/render.");
         assert_eq!(collections[0].tail, ".exr");
         assert_eq!(collections[0].padding, 4);
-        assert_eq!(collections[0].indexes, vec![1001, 1002, 1003, 1004, 1005]);
+        assert_eq!(collections[0].indexes(), vec![1001, 1002, 1003, 1004, 1005]);
     }
 
     #[test]
@@ -213,7 +382,7 @@ mod tests {
             "shot/task/main_v002/render.1004.exr",
             "shot/task/main_v002/render.1005.exr",
         ];
-        let (collections, _remainder) = assemble(&files, None);
+        let (collections, _remainder) = assemble(&files, None).unwrap();
 
         assert_eq!(collections.len(), 2);
         let v1 = Collection::new(
@@ -228,8 +397,8 @@ mod tests {
             4,
             vec![1001, 1002, 1003, 1004, 1005],
         );
-        assert_eq!(collections.contains(&v1), true);
-        assert_eq!(collections.contains(&v2), true);
+        assert!(collections.contains(&v1));
+        assert!(collections.contains(&v2));
     }
 
     #[test]
@@ -244,7 +413,7 @@ mod tests {
             "shot/task/main_v002/render.1004.exr",
             "shot/task/main_v002/render.1005.exr",
         ];
-        let (collections, _remainder) = assemble(&files, None);
+        let (collections, _remainder) = assemble(&files, None).unwrap();
 
         assert_eq!(collections.len(), 2);
         let v1 = Collection::new(
@@ -259,8 +428,8 @@ mod tests {
             4,
             vec![1001, 1002, 1004, 1005],
         );
-        assert_eq!(collections.contains(&v1), true);
-        assert_eq!(collections.contains(&v2), true);
+        assert!(collections.contains(&v1));
+        assert!(collections.contains(&v2));
     }
 
     #[test]
@@ -275,7 +444,7 @@ mod tests {
             files.push(format!("shot/task/main_v005/render.{:04}.exr", i));
             indexes.push(i);
         }
-        let (collections, _remainder) = assemble(&files, None);
+        let (collections, _remainder) = assemble(&files, None).unwrap();
 
         let v1 = Collection::new(
             "shot/task/main_v001/render.".to_string(),
@@ -289,8 +458,179 @@ mod tests {
             4,
             indexes,
         );
-        assert_eq!(collections.contains(&v1), true);
-        assert_eq!(collections.contains(&v2), true);
+        assert!(collections.contains(&v1));
+        assert!(collections.contains(&v2));
+    }
+
+    // Benchmark of assembling a few million items, run with:
+    //
+    //     cargo test --release --features parallel -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn test_large_set_benchmark() {
+        let mut files = vec![];
+        for shot in 0..40 {
+            for version in 1..6 {
+                for frame in 1001..11001 {
+                    files.push(format!(
+                        "seq/sh{:03}/task/main_v{:03}/render.{:04}.exr",
+                        shot, version, frame
+                    ));
+                }
+            }
+        }
+
+        let start = std::time::Instant::now();
+        let (collections, remainder) = assemble(&files, None).unwrap();
+        println!("assemble {} items: {:?}", files.len(), start.elapsed());
+        assert_eq!(collections.iter().filter(|c| c.tail == ".exr").count(), 200);
+        assert!(remainder.is_empty());
+
+        // Adding items one at a time is always serial.
+        let start = std::time::Instant::now();
+        let mut assembler = Assembler::new(None, &AssembleOptions::new()).unwrap();
+        for file in files.iter() {
+            assembler.push(file);
+        }
+        let serial = assembler.finish();
+        println!(
+            "serial assemble {} items: {:?}",
+            files.len(),
+            start.elapsed()
+        );
+        assert_eq!(serial, (collections, remainder));
+    }
+
+    // Long sequences alongside many small collections and unmatched items.
+    fn many_collections(shots: usize, frames: usize, assets: usize) -> Vec<String> {
+        let mut files = vec![];
+        for shot in 0..shots {
+            for frame in 0..frames {
+                files.push(format!("seq/sh{:03}/render.{}.exr", shot, frame));
+            }
+        }
+        // Assets are named by letters so only their versions are numbered,
+        // and two versions are too few to form collections.
+        for asset in 0..assets {
+            let name: String = format!("{:05}", asset)
+                .chars()
+                .map(|c| (b'a' + c.to_digit(10).unwrap() as u8) as char)
+                .collect();
+            files.push(format!("assets/{}/model_v001.abc", name));
+            files.push(format!("assets/{}/model_v002.abc", name));
+            files.push(format!("assets/{}/notes.txt", name));
+        }
+        files
+    }
+
+    // The per-file remainder that hashed lookups replaced, as a baseline:
+    // every member of every collection is formatted to look up each file, and
+    // the remainder is searched linearly for duplicates.
+    fn naive_remainder(files: &[String], collections: &[Collection]) -> Vec<String> {
+        let mut remainder: Vec<String> = vec![];
+        for file in files.iter() {
+            let is_member = collections
+                .iter()
+                .any(|c| c.into_iter().any(|member| &member == file));
+            if !is_member && !remainder.contains(file) {
+                remainder.push(file.to_string());
+            }
+        }
+        remainder.sort_by(|a, b| natural::natural_cmp(a, b));
+        remainder
+    }
+
+    // Benchmark of assembling long sequences alongside many small collections
+    // and unmatched items, which should take close to linear time, against
+    // the naive per-file path on fewer items, as that takes quadratic time.
+    // Run with:
+    //
+    //     cargo test --release -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn test_many_collections_benchmark() {
+        let files = many_collections(100, 10000, 100000);
+        let start = std::time::Instant::now();
+        let (collections, remainder) = assemble(&files, None).unwrap();
+        println!("assemble {} items: {:?}", files.len(), start.elapsed());
+        assert_eq!(collections.iter().filter(|c| c.tail == ".exr").count(), 100);
+        assert_eq!(remainder.len(), 300000);
+
+        let start = std::time::Instant::now();
+        for c in collections.iter().filter(|c| c.tail == ".exr") {
+            assert!(c.contains(&format!("{}5000{}", c.head, c.tail)));
+        }
+        println!("contains: {:?}", start.elapsed());
+
+        let start = std::time::Instant::now();
+        for c in collections.iter().filter(|c| c.tail == ".exr") {
+            let item = format!("{}5000{}", c.head, c.tail);
+            assert!(c.into_iter().any(|member| member == item));
+        }
+        println!("naive contains: {:?}", start.elapsed());
+
+        let files = many_collections(4, 250, 250);
+        let start = std::time::Instant::now();
+        let (collections, remainder) = assemble(&files, None).unwrap();
+        println!("assemble {} items: {:?}", files.len(), start.elapsed());
+
+        let start = std::time::Instant::now();
+        let naive = naive_remainder(&files, &collections);
+        println!(
+            "naive remainder of {} items: {:?}",
+            files.len(),
+            start.elapsed()
+        );
+        assert_eq!(naive, remainder);
+    }
+
+    #[test]
+    fn test_assemble_paths() {
+        let files = vec![
+            Path::new("shot/render.1001.exr"),
+            Path::new("shot/render.1002.exr"),
+            Path::new("shot/render.1003.exr"),
+            Path::new("shot/notes.txt"),
+        ];
+        let (collections, remainder) =
+            assemble_paths(&files, None, &AssembleOptions::new()).unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].paths(), files[..3]);
+        assert_eq!(remainder, vec![PathBuf::from("shot/notes.txt")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_assemble_paths_non_utf8() {
+        use std::ffi::OsString;
+        use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+        let mut files = vec![];
+        for frame in 1001..1004 {
+            let mut name = b"caf\xe9/render.".to_vec();
+            name.extend(frame.to_string().bytes());
+            name.extend(b".exr");
+            files.push(PathBuf::from(OsString::from_vec(name)));
+        }
+        files.push(PathBuf::from(OsString::from_vec(
+            b"caf\xe9/notes.txt".to_vec(),
+        )));
+
+        let (collections, remainder) =
+            assemble_paths(&files, None, &AssembleOptions::new()).unwrap();
+        assert_eq!(collections.len(), 1);
+        let c = &collections[0];
+        assert_eq!(c.head, "caf\u{10FFE9}/render.");
+        assert_eq!(c.paths(), files[..3]);
+        assert!(c.contains_path(&files[1]));
+        assert!(!c.contains_path(&files[3]));
+        assert_eq!(
+            c.format_os(Some("{head}{padding}{tail}"))
+                .unwrap()
+                .as_bytes(),
+            b"caf\xe9/render.%04d.exr"
+        );
+        assert_eq!(remainder, files[3..]);
     }
 
     #[test]
@@ -304,14 +644,11 @@ mod tests {
             "shot/task/main_v002/render.1005.exr",
             "foo",
         ];
-        let (collections, remainder) = assemble(&files, None);
+        let (collections, remainder) = assemble(&files, None).unwrap();
         assert_eq!(collections.len(), 1);
         assert_eq!(remainder.len(), 2);
-        assert_eq!(
-            remainder.contains(&String::from("shot/task/main_v002/render.1005.exr")),
-            true
-        );
-        assert_eq!(remainder.contains(&String::from("foo")), true);
+        assert!(remainder.contains(&String::from("shot/task/main_v002/render.1005.exr")));
+        assert!(remainder.contains(&String::from("foo")));
     }
 
     #[test]
@@ -327,7 +664,8 @@ mod tests {
             "shot/task/main_v002/render.1003.exr",
             "shot/task/main_v003/render.1003.exr",
         ];
-        let (collections, _remainder) = assemble(&files, Some(vec![VERSION_PATTERN.to_string()]));
+        let (collections, _remainder) =
+            assemble(&files, Some(vec![VERSION_PATTERN.to_string()])).unwrap();
         assert_eq!(collections.len(), 3);
 
         let c1001 = Collection::new(
@@ -349,8 +687,599 @@ mod tests {
             vec![1, 2, 3],
         );
 
-        assert_eq!(collections.contains(&c1001), true);
-        assert_eq!(collections.contains(&c1002), true);
-        assert_eq!(collections.contains(&c1003), true);
+        assert!(collections.contains(&c1001));
+        assert!(collections.contains(&c1002));
+        assert!(collections.contains(&c1003));
+    }
+
+    #[test]
+    fn test_parse() {
+        let c = parse("head.%04d.tail [1001-1003, 1005]", None::<&str>).unwrap();
+        assert_eq!(c.head, "head.");
+        assert_eq!(c.tail, ".tail");
+        assert_eq!(c.padding, 4);
+        assert_eq!(c.indexes(), vec![1001, 1002, 1003, 1005]);
+
+        let c = parse("head.%d.tail [1]", None::<&str>).unwrap();
+        assert_eq!(c.padding, 0);
+        assert_eq!(c.indexes(), vec![1]);
+
+        let c = parse(
+            "head.%04d.tail [1001-1005] [1002, 1004]",
+            Some("{head}{padding}{tail} [{range}] [{holes}]"),
+        )
+        .unwrap();
+        assert_eq!(c.indexes(), vec![1001, 1003, 1005]);
+
+        let c = parse("head.tail 1-3", Some("{head}.{tail} {start}-{end}")).unwrap();
+        assert_eq!(c.head, "head");
+        assert_eq!(c.tail, "tail");
+        assert_eq!(c.indexes(), vec![1, 2, 3]);
+
+        assert!(matches!(
+            parse("head.%04d.tail", None::<&str>),
+            Err(Error::ParseFailed { .. })
+        ));
+        assert!(matches!(
+            parse("head.%04d.tail", Some("{head}{padding}{tail} {FOO}")),
+            Err(Error::UnknownFormatKey(key)) if key == "FOO"
+        ));
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let collections = [
+            Collection::new(
+                "shot/task/main_v001/render.".to_string(),
+                ".exr".to_string(),
+                4,
+                vec![1001, 1002, 1003, 1005, 1008, 1009],
+            ),
+            Collection::new(
+                "render (1) [v2].".to_string(),
+                ".exr".to_string(),
+                0,
+                vec![7],
+            ),
+        ];
+        let formats = [
+            "{head}{padding}{tail} [{ranges}]",
+            "{head}{padding}{tail} [{range}] [{holes}]",
+            "{head}{padding}{tail} [{start}-{end}] [{holes}]",
+        ];
+        for collection in collections.iter() {
+            let formatted = collection.format::<&str>(None).unwrap();
+            assert_eq!(
+                parse(&formatted, None::<&str>).ok().as_ref(),
+                Some(collection)
+            );
+
+            for fmt in formats.iter() {
+                let formatted = collection.format(Some(fmt)).unwrap();
+                assert_eq!(parse(&formatted, Some(fmt)).ok().as_ref(), Some(collection));
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_steps() {
+        let collection = Collection::new(
+            "render.".to_string(),
+            ".exr".to_string(),
+            4,
+            vec![1001, 1003, 1005, 1007, 1011, 1021],
+        );
+        let formats = [
+            "{head}{padding}{tail} [{ranges}]",
+            "{head}{padding}{tail} [{range}] [{holes}]",
+            "{head}{padding}{tail} [{start}-{end}x{step}] [{holes}]",
+        ];
+        for fmt in formats.iter() {
+            let formatted = collection.format(Some(fmt)).unwrap();
+            assert_eq!(parse(&formatted, Some(fmt)).unwrap(), collection);
+        }
+        assert_eq!(
+            parse("render.%04d.exr [1001-1099x2]", None::<&str>)
+                .unwrap()
+                .indexes()
+                .len(),
+            50
+        );
+        assert!(parse("render.%04d.exr [1001-1099x0]", None::<&str>).is_err());
+    }
+
+    #[test]
+    fn test_assemble_minimum_items() {
+        let files = vec!["render.1001.exr", "render.1002.exr", "foo"];
+        let (collections, remainder) = assemble(&files, None).unwrap();
+        assert_eq!(collections.len(), 0);
+        assert_eq!(remainder.len(), 3);
+
+        let options = AssembleOptions::new().minimum_items(2);
+        let (collections, remainder) = assemble_with(&files, None, &options).unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].indexes(), vec![1001, 1002]);
+        assert_eq!(remainder, vec!["foo".to_string()]);
+    }
+
+    #[test]
+    fn test_assemble_ambiguous_padding() {
+        let files = vec![
+            "render.998.exr",
+            "render.999.exr",
+            "render.1000.exr",
+            "render.1001.exr",
+        ];
+        let (collections, _remainder) = assemble(&files, None).unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].padding, 0);
+        assert_eq!(collections[0].indexes(), vec![998, 999, 1000, 1001]);
+
+        let files = vec!["render.1001.exr", "render.1002.exr", "render.1003.exr"];
+        let (collections, _remainder) = assemble(&files, None).unwrap();
+        assert_eq!(collections[0].padding, 4);
+
+        let options = AssembleOptions::new().assume_padded_when_ambiguous(false);
+        let (collections, _remainder) = assemble_with(&files, None, &options).unwrap();
+        assert_eq!(collections[0].padding, 0);
+    }
+
+    #[test]
+    fn test_assemble_merge_unpadded() {
+        let files = vec![
+            "render.0998.exr",
+            "render.0999.exr",
+            "render.1000.exr",
+            "render.1001.exr",
+        ];
+        let options = AssembleOptions::new().minimum_items(2);
+        let (collections, _remainder) = assemble_with(&files, None, &options).unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].padding, 4);
+        assert_eq!(collections[0].indexes(), vec![998, 999, 1000, 1001]);
+
+        let options = options.merge_unpadded(false);
+        let (collections, remainder) = assemble_with(&files, None, &options).unwrap();
+        assert_eq!(collections.len(), 2);
+        assert_eq!(remainder.len(), 0);
+    }
+
+    #[test]
+    fn test_assemble_case_sensitive() {
+        let files = vec!["render.1001.exr", "render.1002.EXR", "render.1003.exr"];
+        let (collections, remainder) = assemble(&files, None).unwrap();
+        assert_eq!(collections.len(), 0);
+        assert_eq!(remainder.len(), 3);
+
+        let options = AssembleOptions::new().case_sensitive(false);
+        let (collections, remainder) = assemble_with(&files, None, &options).unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].tail, ".exr");
+        assert_eq!(collections[0].indexes(), vec![1001, 1002, 1003]);
+        assert_eq!(remainder.len(), 0);
+
+        // Members are written in the case of the first item, which contains
+        // compares exactly.
+        let files = vec!["R.1001.exr", "r.1002.exr", "r.1003.EXR"];
+        let (collections, _) = assemble_with(&files, None, &options).unwrap();
+        let c = &collections[0];
+        assert_eq!((c.head(), c.tail()), ("R.", ".exr"));
+        assert!(c.contains("R.1003.exr"));
+        assert!(!c.contains("r.1003.EXR"));
+    }
+
+    #[test]
+    fn test_assemble_invalid_patterns() {
+        let files = vec!["render.1001.exr"];
+        assert!(matches!(
+            assemble(&files, Some(vec!["v(".to_string()])),
+            Err(Error::InvalidPattern(pattern, _)) if pattern == "v("
+        ));
+        assert!(matches!(
+            assemble(&files, Some(vec!["v(\\d+)".to_string()])),
+            Err(Error::MissingGroup { group, .. }) if group == "index"
+        ));
+    }
+
+    #[test]
+    fn test_assemble_order() {
+        let files = vec![
+            "foo",
+            "shot/task/main_v010/render.1001.exr",
+            "shot/task/main_v010/render.1002.exr",
+            "shot/task/main_v010/render.1003.exr",
+            "shot/task/main_v002/render.1001.exr",
+            "shot/task/main_v002/render.1002.exr",
+            "shot/task/main_v002/render.1003.exr",
+            "bar",
+            "shot/task/main_v001/render.2001.exr",
+        ];
+        let (collections, remainder) = assemble(&files, None).unwrap();
+        let heads: Vec<&str> = collections.iter().map(|c| c.head.as_str()).collect();
+        assert_eq!(
+            heads,
+            vec!["shot/task/main_v002/render.", "shot/task/main_v010/render."]
+        );
+        assert_eq!(
+            remainder,
+            vec!["bar", "foo", "shot/task/main_v001/render.2001.exr"]
+        );
+
+        let options = AssembleOptions::new().order(CollectionOrder::FirstAppearance);
+        let (collections, remainder) = assemble_with(&files, None, &options).unwrap();
+        let heads: Vec<&str> = collections.iter().map(|c| c.head.as_str()).collect();
+        assert_eq!(
+            heads,
+            vec!["shot/task/main_v010/render.", "shot/task/main_v002/render."]
+        );
+        assert_eq!(
+            remainder,
+            vec!["foo", "bar", "shot/task/main_v001/render.2001.exr"]
+        );
+    }
+
+    #[test]
+    fn test_assemble_large_indexes() {
+        let files = vec![
+            "cam_20240101120000.jpg",
+            "cam_20240101120001.jpg",
+            "cam_20240101120002.jpg",
+        ];
+        let (collections, remainder) = assemble(&files, None).unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(
+            collections[0].indexes(),
+            vec![20240101120000, 20240101120001, 20240101120002]
+        );
+        assert_eq!(remainder.len(), 0);
+    }
+
+    #[test]
+    fn test_assemble_negative_indexes() {
+        let files = vec![
+            "sim.-0002.bgeo",
+            "sim.-0001.bgeo",
+            "sim.0000.bgeo",
+            "sim.0001.bgeo",
+        ];
+        let (collections, remainder) = assemble(&files, None).unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].head, "sim.");
+        assert_eq!(collections[0].padding, 4);
+        assert_eq!(collections[0].indexes(), vec![-2, -1, 0, 1]);
+        assert_eq!(remainder.len(), 0);
+
+        let members: Vec<String> = (&collections[0]).into_iter().collect();
+        assert_eq!(members, files);
+
+        let formatted = collections[0].format::<&str>(None).unwrap();
+        assert_eq!(formatted, "sim.%04d.bgeo [-2-1]");
+        assert_eq!(parse(&formatted, None::<&str>).unwrap(), collections[0]);
+    }
+
+    #[test]
+    fn test_assemble_hyphen_separated() {
+        let files = vec!["plate-1001.exr", "plate-1002.exr", "plate-1003.exr"];
+        let (collections, remainder) = assemble(&files, None).unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(
+            collections[0].format::<&str>(None).unwrap(),
+            "plate-%04d.exr [1001-1003]"
+        );
+        assert!(remainder.is_empty());
+
+        let files = vec![
+            "log-2024-01-01.txt",
+            "log-2024-01-02.txt",
+            "log-2024-01-03.txt",
+        ];
+        let (collections, remainder) = assemble(&files, None).unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(
+            collections[0].format::<&str>(None).unwrap(),
+            "log-2024-01-%02d.txt [1-3]"
+        );
+        assert!(remainder.is_empty());
+
+        let files = vec!["cache_-2.abc", "cache_-1.abc", "cache_0.abc", "-3.abc"];
+        let options = AssembleOptions::new().minimum_items(2);
+        let (collections, _remainder) = assemble_with(&files, None, &options).unwrap();
+        assert_eq!(collections[0].head, "cache_");
+        assert_eq!(collections[0].indexes(), vec![-2, -1, 0]);
+    }
+
+    #[test]
+    fn test_parse_negative_ranges() {
+        let c = parse("sim.%03d.bgeo [-10--8, -5, 2-3]", None::<&str>).unwrap();
+        assert_eq!(c.padding, 3);
+        assert_eq!(c.indexes(), vec![-10, -9, -8, -5, 2, 3]);
+        assert_eq!(
+            c.format::<&str>(None).unwrap(),
+            "sim.%03d.bgeo [-10--8, -5, 2-3]"
+        );
+    }
+
+    #[test]
+    fn test_assemble_subframes() {
+        let files = vec![
+            "sim.1001.00.bgeo",
+            "sim.1001.25.bgeo",
+            "sim.1001.50.bgeo",
+            "sim.1001.75.bgeo",
+            "sim.1002.00.bgeo",
+            "sim.1002.50.bgeo",
+        ];
+        let options = AssembleOptions::new().subframes(true);
+        let (collections, remainder) = assemble_with(&files, None, &options).unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(remainder.len(), 0);
+
+        let c = &collections[0];
+        assert_eq!(c.head, "sim.");
+        assert_eq!(c.tail, ".bgeo");
+        assert_eq!(c.padding, 4);
+        assert_eq!(c.kind, IndexKind::Subframe { padding: 2 });
+        assert_eq!(
+            c.indexes(),
+            vec![100100, 100125, 100150, 100175, 100200, 100250]
+        );
+        assert_eq!(c.step(), 25);
+        assert_eq!(c.subframes()[1].to_f64(), 1001.25);
+        assert_eq!(c.holes().indexes(), vec![100225]);
+
+        let members: Vec<String> = c.into_iter().collect();
+        assert_eq!(members, files);
+
+        let formatted = c.format::<&str>(None).unwrap();
+        assert_eq!(formatted, "sim.%04d.%02d.bgeo [1001-1002, 1002.5 @0.25]");
+        assert_eq!(&parse(&formatted, None::<&str>).unwrap(), c);
+        assert_eq!(
+            c.format(Some("{range} [{holes}]")).unwrap(),
+            "1001-1002.5 @0.25 [1002.25]"
+        );
+        assert!(c.match_item("sim.1010.75.bgeo").unwrap().is_some());
+        assert!(c.match_item("sim.1010.7.bgeo").unwrap().is_none());
+        assert!(c.match_item("sim.1010.bgeo").unwrap().is_none());
+
+        let (collections, _remainder) = assemble(&files, None).unwrap();
+        assert!(collections.iter().all(|c| c.kind == IndexKind::Frame));
+    }
+
+    #[test]
+    fn test_assemble_subframes_with_versions() {
+        let mut files = vec![];
+        for version in 1..3 {
+            for frame in 1001..1004 {
+                files.push(format!("comp_v{:03}.{}.exr", version, frame));
+            }
+        }
+        files.extend(
+            ["00", "25", "50"]
+                .iter()
+                .map(|subframe| format!("sim_v002.1001.{}.bgeo", subframe)),
+        );
+        let options = AssembleOptions::new().subframes(true);
+        let (collections, remainder) = assemble_with(&files, None, &options).unwrap();
+        let formatted: Vec<String> = collections
+            .iter()
+            .map(|c| c.format::<&str>(None).unwrap())
+            .collect();
+        assert_eq!(
+            formatted,
+            vec![
+                "comp_v001.%04d.exr [1001-1003]",
+                "comp_v002.%04d.exr [1001-1003]",
+                "sim_v002.%04d.%02d.bgeo [1001-1001.5 @0.25]",
+            ]
+        );
+        assert!(remainder.is_empty());
+    }
+
+    #[test]
+    fn test_parse_subframes() {
+        let c = parse("cache.%04d.%03d.vdb [10-11 @0.5]", None::<&str>).unwrap();
+        assert_eq!(c.kind, IndexKind::Subframe { padding: 3 });
+        assert_eq!(c.indexes(), vec![10000, 10500, 11000]);
+        let members: Vec<String> = c.into_iter().collect();
+        assert_eq!(
+            members,
+            vec![
+                "cache.0010.000.vdb",
+                "cache.0010.500.vdb",
+                "cache.0011.000.vdb"
+            ]
+        );
+
+        let c = parse("sim.1001.25.bgeo", Some("{head}{start}{tail}")).unwrap();
+        assert_eq!(c.kind, IndexKind::Subframe { padding: 2 });
+    }
+
+    #[test]
+    fn test_assemble_udim() {
+        let files = vec![
+            "tex/diffuse.1001.tx",
+            "tex/diffuse.1002.tx",
+            "tex/diffuse.1003.tx",
+            "tex/diffuse.1011.tx",
+            "tex/diffuse.1013.tx",
+            "tex/diffuse.0999.tx",
+            "tex/diffuse.2001.tx",
+        ];
+        let options = AssembleOptions::new().udim(true);
+        let (collections, remainder) = assemble_with(&files, None, &options).unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(
+            remainder,
+            vec!["tex/diffuse.0999.tx", "tex/diffuse.2001.tx"]
+        );
+
+        let c = &collections[0];
+        assert_eq!(c.kind, IndexKind::Udim);
+        assert_eq!(c.padding, 4);
+        assert_eq!(c.indexes(), vec![1001, 1002, 1003, 1011, 1013]);
+        assert_eq!(c.tile_columns(), Some((0, 2)));
+        assert_eq!(c.tile_rows(), Some((0, 1)));
+        assert_eq!(c.tiles()[3], UdimTile { u: 0, v: 1 });
+        assert_eq!(c.holes().indexes(), vec![1012]);
+        assert!(!c.is_contiguous());
+
+        let formatted = c.format::<&str>(None).unwrap();
+        assert_eq!(formatted, "tex/diffuse.<UDIM>.tx [1001-1003, 1011, 1013]");
+        assert_eq!(&parse(&formatted, None::<&str>).unwrap(), c);
+        assert_eq!(
+            c.format(Some("{head}{uvtile}{tail} [{holes}]")).unwrap(),
+            "tex/diffuse.u<U>_v<V>.tx [1012]"
+        );
+
+        let members: Vec<String> = c.into_iter().collect();
+        assert_eq!(members, files[..5]);
+
+        let (collections, _remainder) = assemble(&files, None).unwrap();
+        assert!(collections.iter().all(|c| c.kind == IndexKind::Frame));
+    }
+
+    #[test]
+    fn test_parse_udim() {
+        let c = parse("diffuse.<UDIM>.tx [1001-1002, 1011-1012]", None::<&str>).unwrap();
+        assert_eq!(c.kind, IndexKind::Udim);
+        assert_eq!(c.padding, 4);
+        assert_eq!(c.indexes(), vec![1001, 1002, 1011, 1012]);
+        assert!(c.is_contiguous());
+
+        let c = parse(
+            "diffuse_u<U>_v<V>.tx [1001-1003]",
+            Some("{head}{uvtile}{tail} [{ranges}]"),
+        )
+        .unwrap();
+        assert_eq!(c.kind, IndexKind::Udim);
+        assert_eq!(c.head, "diffuse_");
+    }
+
+    #[test]
+    fn test_assemble_with_reasons() {
+        let files = vec![
+            "render.1001.exr",
+            "render.1002.exr",
+            "render.1003.exr",
+            "comp_v01.1001.nk",
+            "comp_v01.1002.nk",
+            "notes.txt",
+            "cache.123456789012345678901234567890.abc",
+        ];
+        let (collections, remainder) =
+            assemble_with_reasons(&files, None, &AssembleOptions::new()).unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(
+            remainder,
+            vec![
+                Remainder {
+                    item: "cache.123456789012345678901234567890.abc".to_string(),
+                    reason: RemainderReason::IndexOverflow {
+                        head: "cache.".to_string(),
+                        tail: ".abc".to_string(),
+                        padding: 0,
+                    },
+                },
+                Remainder {
+                    item: "comp_v01.1001.nk".to_string(),
+                    reason: RemainderReason::BelowMinimumItems(Collection::new(
+                        "comp_v01.".to_string(),
+                        ".nk".to_string(),
+                        0,
+                        vec![1001, 1002],
+                    )),
+                },
+                Remainder {
+                    item: "comp_v01.1002.nk".to_string(),
+                    reason: RemainderReason::BelowMinimumItems(Collection::new(
+                        "comp_v01.".to_string(),
+                        ".nk".to_string(),
+                        0,
+                        vec![1001, 1002],
+                    )),
+                },
+                Remainder {
+                    item: "notes.txt".to_string(),
+                    reason: RemainderReason::NoMatch,
+                },
+            ]
+        );
+        assert_eq!(
+            remainder[1].reason.to_string(),
+            "too few items in comp_v01.%d.nk [1001-1002]"
+        );
+
+        let (_, plain_remainder) = assemble(&files, None).unwrap();
+        let items: Vec<String> = remainder.into_iter().map(|r| r.item).collect();
+        assert_eq!(items, plain_remainder);
+    }
+
+    #[test]
+    fn test_assemble_selection() {
+        let mut files = vec![];
+        for version in 1..4 {
+            for frame in 1001..1006 {
+                files.push(format!("main_v{:03}/render.{}.exr", version, frame));
+            }
+        }
+
+        let (collections, _) = assemble(&files, None).unwrap();
+        assert_eq!(collections.len(), 8);
+
+        let frames = vec![
+            "main_v001/render.",
+            "main_v002/render.",
+            "main_v003/render.",
+        ];
+        for selection in [MatchSelection::Rightmost, MatchSelection::MostVarying] {
+            let options = AssembleOptions::new().selection(selection);
+            let (collections, remainder) = assemble_with(&files, None, &options).unwrap();
+            let heads: Vec<&str> = collections.iter().map(|c| c.head.as_str()).collect();
+            assert_eq!(heads, frames);
+            assert!(remainder.is_empty());
+        }
+
+        let options = AssembleOptions::new().selection(MatchSelection::Leftmost);
+        let (collections, remainder) = assemble_with(&files, None, &options).unwrap();
+        assert_eq!(collections.len(), 5);
+        assert!(collections.iter().all(|c| c.head == "main_v"));
+        assert!(remainder.is_empty());
+
+        // Versions vary the most when there are more versions than frames.
+        let files = vec![
+            "take1/shot.1001.exr",
+            "take2/shot.1001.exr",
+            "take3/shot.1001.exr",
+            "take4/shot.1001.exr",
+            "take4/shot.1002.exr",
+        ];
+        let options = AssembleOptions::new()
+            .selection(MatchSelection::MostVarying)
+            .minimum_items(2);
+        let (collections, remainder) = assemble_with(&files, None, &options).unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].head, "take");
+        assert_eq!(collections[0].indexes(), vec![1, 2, 3, 4]);
+        assert_eq!(remainder, vec!["take4/shot.1002.exr"]);
+
+        // Every item chooses the same number as the items it differs from
+        // only by its numbers, so none are split from their siblings.
+        let files = vec![
+            "a_v001.1001",
+            "a_v001.1002",
+            "a_v002.1001",
+            "a_v002.1002",
+            "a_v003.1001",
+        ];
+        let (collections, remainder) = assemble_with(&files, None, &options).unwrap();
+        let formatted: Vec<String> = collections
+            .iter()
+            .map(|c| c.format::<&str>(None).unwrap())
+            .collect();
+        assert_eq!(formatted, vec!["a_v%03d.1001 [1-3]", "a_v%03d.1002 [1-2]"]);
+        assert!(remainder.is_empty());
+        let mut members: Vec<String> = collections.iter().flatten().collect();
+        members.sort();
+        assert_eq!(members, files);
     }
 }
@@ -1,11 +1,17 @@
-use cliquers;
 use std::error::Error;
+use std::ffi::{OsStr, OsString};
 use std::fs;
-use std::io;
+use std::io::{self, Write};
 use std::path;
+use std::process;
 use structopt::StructOpt;
 use walkdir::WalkDir;
 
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
+
 // List files grouping filesequences together.
 #[derive(StructOpt)]
 struct Cli {
@@ -13,6 +19,10 @@ struct Cli {
     #[structopt(short = "s", long = "show-remainder")]
     show_remainder: bool,
 
+    /// Print the reason each file is not in a filesequence, with --show-remainder
+    #[structopt(long = "reasons")]
+    reasons: bool,
+
     /// Recurse down subdirectories
     #[structopt(short = "r", long = "recurse")]
     recurse: bool,
@@ -21,65 +31,213 @@ struct Cli {
     #[structopt(short = "f", long = "format")]
     format: Option<String>,
 
-    /// Optional custom pattern for grouping collections of files, default pattern: "(?P<index>(?P<padding>0*)\d+)"
+    /// Style of {padding} in filesequences: printf, hash, at, houdini, nuke, rv, blender or python
+    #[structopt(long = "padding-style", default_value = "printf", parse(try_from_str = parse_padding_style))]
+    padding_style: cliquers::PaddingStyle,
+
+    /// Optional custom pattern for grouping collections of files, default pattern: "(?:(?:^|[._])(?P<sign>-))?(?P<index>(?P<padding>0*)\d+)"
     #[structopt(short = "p", long = "patterns")]
     patterns: Option<Vec<String>>,
 
+    /// Preset pattern for grouping collections of files, combined with any custom patterns: digits, frames, versions, udim, houdini or blender
+    #[structopt(long = "preset", value_name = "preset", number_of_values = 1, parse(try_from_str = parse_preset))]
+    presets: Vec<cliquers::Pattern>,
+
+    /// Minimum number of files in a filesequence
+    #[structopt(short = "m", long = "minimum-items", default_value = "3")]
+    minimum_items: usize,
+
+    /// Keep ambiguous unpadded filesequences, such as 1001-1005, unpadded
+    #[structopt(long = "no-assume-padded")]
+    no_assume_padded: bool,
+
+    /// Group files whose names differ only in case
+    #[structopt(short = "i", long = "ignore-case")]
+    ignore_case: bool,
+
+    /// Do not merge unpadded files into padded filesequences of the same width
+    #[structopt(long = "no-merge-unpadded")]
+    no_merge_unpadded: bool,
+
+    /// Group subframe files, such as sim.1001.25.bgeo, into subframe filesequences
+    #[structopt(long = "subframes")]
+    subframes: bool,
+
+    /// Group UDIM texture tiles, such as diffuse.1001.tx, into UDIM filesequences
+    #[structopt(long = "udim")]
+    udim: bool,
+
+    /// Which numbers to group files by when several match: all, leftmost, rightmost or most-varying
+    #[structopt(long = "select", default_value = "all", parse(try_from_str = parse_selection))]
+    select: cliquers::MatchSelection,
+
     /// The path to list files and filesequences under
     #[structopt(parse(from_os_str))]
     paths: Vec<path::PathBuf>,
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+// Parse the name of a match selection given to --select.
+fn parse_selection(value: &str) -> std::result::Result<cliquers::MatchSelection, String> {
+    match value {
+        "all" => Ok(cliquers::MatchSelection::All),
+        "leftmost" => Ok(cliquers::MatchSelection::Leftmost),
+        "rightmost" => Ok(cliquers::MatchSelection::Rightmost),
+        "most-varying" => Ok(cliquers::MatchSelection::MostVarying),
+        value => Err(format!("unknown selection \"{}\"", value)),
+    }
+}
+
+// Parse the name of a preset pattern given to --preset.
+fn parse_preset(value: &str) -> std::result::Result<cliquers::Pattern, String> {
+    match value {
+        "digits" => Ok(cliquers::Pattern::Digits),
+        "frames" => Ok(cliquers::Pattern::Frames),
+        "versions" => Ok(cliquers::Pattern::Versions),
+        "udim" => Ok(cliquers::Pattern::Udim),
+        "houdini" => Ok(cliquers::Pattern::Houdini),
+        "blender" => Ok(cliquers::Pattern::Blender),
+        value => Err(format!("unknown preset \"{}\"", value)),
+    }
+}
+
+// Parse the name of a padding style given to --padding-style.
+fn parse_padding_style(value: &str) -> std::result::Result<cliquers::PaddingStyle, String> {
+    match value {
+        "printf" => Ok(cliquers::PaddingStyle::Printf),
+        "hash" => Ok(cliquers::PaddingStyle::Hash),
+        "at" => Ok(cliquers::PaddingStyle::AtSign),
+        "houdini" => Ok(cliquers::PaddingStyle::Houdini),
+        "nuke" => Ok(cliquers::PaddingStyle::Nuke),
+        "rv" => Ok(cliquers::PaddingStyle::Rv),
+        "blender" => Ok(cliquers::PaddingStyle::Blender),
+        "python" => Ok(cliquers::PaddingStyle::Python),
+        value => Err(format!("unknown padding style \"{}\"", value)),
+    }
+}
+
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("cliquers: {}", error);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<()> {
     let args = Cli::from_args();
+    let options = cliquers::AssembleOptions::new()
+        .minimum_items(args.minimum_items)
+        .assume_padded_when_ambiguous(!args.no_assume_padded)
+        .case_sensitive(!args.ignore_case)
+        .merge_unpadded(!args.no_merge_unpadded)
+        .subframes(args.subframes)
+        .udim(args.udim)
+        .selection(args.select);
 
-    match args.recurse {
-        true => {
-            for path in args.paths {
-                for dir in WalkDir::new(path)
+    let dirs: Vec<path::PathBuf> = match args.recurse {
+        true => args
+            .paths
+            .iter()
+            .flat_map(|path| {
+                WalkDir::new(path)
                     .into_iter()
                     .filter_map(|e| e.ok())
-                    .filter_map(|e| match e.file_type().is_dir() {
-                        true => Some(e),
-                        false => None,
-                    })
-                {
-                    let entries = fs::read_dir(dir.path())?
-                        .map(|res| res.map(|e| e.path().to_str().unwrap().to_string()))
-                        .collect::<Result<Vec<_>, io::Error>>()?;
-
-                    let (collections, remainders) =
-                        cliquers::assemble(&entries, args.patterns.to_owned());
-                    for c in collections.iter() {
-                        println!("{}", c.format(args.format.to_owned()));
-                    }
-                    if args.show_remainder {
-                        for r in remainders.iter() {
-                            println!("{}", r);
-                        }
-                    }
-                }
-            }
+                    .filter(|e| e.file_type().is_dir())
+                    .map(|e| e.path().to_path_buf())
+            })
+            .collect(),
+        false => args.paths.to_owned(),
+    };
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for lines in list_all(&dirs, &args, &options) {
+        for line in lines? {
+            write_line(&mut stdout, &line)?;
         }
-        false => {
-            for path in args.paths {
-                let entries = fs::read_dir(path)?
-                    .map(|res| res.map(|e| e.path().to_str().unwrap().to_string()))
-                    .collect::<Result<Vec<_>, io::Error>>()?;
-
-                let (collections, remainders) =
-                    cliquers::assemble(&entries, args.patterns.to_owned());
-                for c in collections.iter() {
-                    println!("{}", c.format(args.format.to_owned()));
-                }
-                if args.show_remainder {
-                    for r in remainders.iter() {
-                        println!("{}", r);
-                    }
-                }
+    }
+
+    Ok(())
+}
+
+// Return the lines listing each directory, in the order of dirs.
+#[cfg(not(feature = "parallel"))]
+fn list_all<'a>(
+    dirs: &'a [path::PathBuf],
+    args: &'a Cli,
+    options: &'a cliquers::AssembleOptions,
+) -> impl Iterator<Item = Result<Vec<OsString>>> + 'a {
+    dirs.iter().map(move |dir| list(dir, args, options))
+}
+
+// Return the lines listing each directory, in the order of dirs. Directories
+// are listed on multiple threads.
+#[cfg(feature = "parallel")]
+fn list_all(
+    dirs: &[path::PathBuf],
+    args: &Cli,
+    options: &cliquers::AssembleOptions,
+) -> impl Iterator<Item = Result<Vec<OsString>>> {
+    let lines: Vec<Result<Vec<OsString>>> = dirs
+        .par_iter()
+        .map(|dir| list(dir, args, options))
+        .collect();
+    lines.into_iter()
+}
+
+// Return the lines listing the filesequences, and optionally the remainder,
+// of a directory.
+fn list(
+    path: &path::Path,
+    args: &Cli,
+    options: &cliquers::AssembleOptions,
+) -> Result<Vec<OsString>> {
+    let entries = fs::read_dir(path)?
+        .map(|res| res.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let mut assembler = cliquers::Assembler::new(patterns(args), options)?;
+    for entry in entries.iter() {
+        assembler.push_path(entry);
+    }
+    let (collections, remainders) = assembler.finish_with_reasons();
+    let mut lines = vec![];
+    for c in collections.iter() {
+        lines.push(c.format_os_with(args.format.to_owned(), args.padding_style)?);
+    }
+    if args.show_remainder {
+        for r in remainders.iter() {
+            let mut line = r.path().into_os_string();
+            if args.reasons {
+                line.push(format!("\t{}", r.reason));
             }
+            lines.push(line);
         }
     }
 
-    Ok(())
+    Ok(lines)
+}
+
+// Return the preset patterns followed by the custom patterns, or None to use
+// the default pattern if there are neither.
+fn patterns(args: &Cli) -> Option<Vec<String>> {
+    if args.presets.is_empty() {
+        return args.patterns.to_owned();
+    }
+    let mut patterns: Vec<String> = args.presets.iter().map(|&preset| preset.into()).collect();
+    patterns.extend(args.patterns.iter().flatten().cloned());
+    Some(patterns)
+}
+
+// Write line followed by a newline, keeping any bytes of names that are not
+// valid UTF-8.
+#[cfg(unix)]
+fn write_line(out: &mut impl Write, line: &OsStr) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    out.write_all(line.as_bytes())?;
+    out.write_all(b"\n")
+}
+
+#[cfg(not(unix))]
+fn write_line(out: &mut impl Write, line: &OsStr) -> io::Result<()> {
+    writeln!(out, "{}", line.to_string_lossy())
 }
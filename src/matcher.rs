@@ -0,0 +1,170 @@
+use regex::{Captures, Regex};
+
+use crate::udim;
+use crate::{Collection, Error, IndexKind, PaddingStyle};
+
+// Compiled matcher for items belonging to a collection. The head and tail are
+// matched literally, so building a matcher once and reusing it avoids
+// recompiling a regex for every item checked. Items with the padding of the
+// collection written in place of the index in any PaddingStyle, such as
+// render.####.exr, match as well.
+#[derive(Debug, Clone)]
+pub struct CollectionMatcher {
+    regex: Regex,
+    padding: i32,
+    kind: IndexKind,
+}
+
+impl CollectionMatcher {
+    pub fn new(collection: &Collection) -> Result<CollectionMatcher, Error> {
+        let subframe = match collection.kind {
+            IndexKind::Frame | IndexKind::Udim => String::new(),
+            IndexKind::Subframe { padding } => format!("\\.(?P<subframe>\\d{{{}}})", padding),
+        };
+        // RV tokens match any range of frames before the padding.
+        let mut tokens: Vec<String> = vec![];
+        for style in PaddingStyle::ALL.iter() {
+            if let Ok(token) = style.token(collection.padding, collection.kind, "") {
+                let token = match style {
+                    PaddingStyle::Rv => format!("-?\\d+--?\\d+(?:x\\d+)?{}", regex::escape(&token)),
+                    _ => regex::escape(&token),
+                };
+                if !tokens.contains(&token) {
+                    tokens.push(token);
+                }
+            }
+        }
+        // Blender tokens follow the head without the dot at its end, such as
+        // render####.exr.
+        let blender = match (
+            collection.head.strip_suffix('.'),
+            PaddingStyle::Blender.token(collection.padding, collection.kind, ""),
+        ) {
+            (Some(head), Ok(token)) => format!(
+                "|{}(?P<blender_token>{})",
+                regex::escape(head),
+                regex::escape(&token)
+            ),
+            _ => String::new(),
+        };
+        let regex_str = format!(
+            "^(?:{0}(?:(?P<index>-?(?P<padding>0*)\\d+){1}|(?P<token>{2})){3}){4}$",
+            regex::escape(&collection.head),
+            subframe,
+            tokens.join("|"),
+            blender,
+            regex::escape(&collection.tail)
+        );
+        let regex = Regex::new(&regex_str)
+            .map_err(|error| Error::InvalidPattern(regex_str.to_string(), error))?;
+        Ok(CollectionMatcher {
+            regex,
+            padding: collection.padding,
+            kind: collection.kind,
+        })
+    }
+
+    // Return the captures of item if it has the head, tail and padding of the
+    // collection. The index does not need to be one of the collection indexes,
+    // but must be a tile number for UDIM collections. Items with a padding
+    // token have a "token" group, or "blender_token" for Blender tokens,
+    // rather than an "index" group.
+    pub fn match_item<'t>(&self, item: &'t str) -> Option<Captures<'t>> {
+        let capture = self.regex.captures(item)?;
+        if capture.name("token").is_some() || capture.name("blender_token").is_some() {
+            return Some(capture);
+        }
+        let index = capture.name("index")?.as_str().trim_start_matches('-');
+        let padded = capture
+            .name("padding")
+            .is_some_and(|padding| !padding.as_str().is_empty());
+
+        if self.padding == 0 {
+            if padded {
+                return None;
+            }
+        } else if index.chars().count() != self.padding as usize {
+            return None;
+        }
+        if self.kind == IndexKind::Udim
+            && !capture
+                .name("index")?
+                .as_str()
+                .parse::<i64>()
+                .is_ok_and(udim::is_udim)
+        {
+            return None;
+        }
+        Some(capture)
+    }
+
+    // Split items into those that match and those that do not, preserving
+    // their order.
+    pub fn match_items<'t, T: AsRef<str>>(&self, items: &'t [T]) -> (Vec<&'t str>, Vec<&'t str>) {
+        items
+            .iter()
+            .map(|item| item.as_ref())
+            .partition(|item| self.match_item(item).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_literal() {
+        let c = Collection::new(
+            "shot(1)/render+v2.".to_string(),
+            ".exr".to_string(),
+            4,
+            vec![1001, 1002],
+        );
+        let matcher = CollectionMatcher::new(&c).unwrap();
+        assert!(matcher.match_item("shot(1)/render+v2.1010.exr").is_some());
+        assert!(matcher.match_item("shot1/renderv2.1010.exr").is_none());
+        assert!(matcher.match_item("shot(1)/render+v2.1010xexr").is_none());
+
+        let c = Collection::new("a.".to_string(), ".exr".to_string(), 4, vec![1001]);
+        let matcher = CollectionMatcher::new(&c).unwrap();
+        assert!(matcher.match_item("abc1010.exr").is_none());
+    }
+
+    #[test]
+    fn test_match_padding() {
+        let c = Collection::new("head.".to_string(), ".tail".to_string(), 0, vec![1, 2]);
+        let matcher = CollectionMatcher::new(&c).unwrap();
+        assert!(matcher.match_item("head.1.tail").is_some());
+        assert!(matcher.match_item("head.1010.tail").is_some());
+        assert!(matcher.match_item("head.01.tail").is_none());
+
+        let c = Collection::new("head.".to_string(), ".tail".to_string(), 4, vec![1, 2]);
+        let matcher = CollectionMatcher::new(&c).unwrap();
+        assert!(matcher.match_item("head.0001.tail").is_some());
+        assert!(matcher.match_item("head.1010.tail").is_some());
+        assert!(matcher.match_item("head.01.tail").is_none());
+        assert!(matcher.match_item("head.10100.tail").is_none());
+    }
+
+    #[test]
+    fn test_match_items() {
+        let c = Collection::new("head.".to_string(), ".tail".to_string(), 4, vec![1]);
+        let matcher = CollectionMatcher::new(&c).unwrap();
+        let items = vec!["head.0001.tail", "foo", "head.0002.tail", "head.3.tail"];
+        let (matched, unmatched) = matcher.match_items(&items);
+        assert_eq!(matched, vec!["head.0001.tail", "head.0002.tail"]);
+        assert_eq!(unmatched, vec!["foo", "head.3.tail"]);
+    }
+
+    #[test]
+    fn test_match_udim() {
+        let c = Collection::new("diffuse.".to_string(), ".tx".to_string(), 4, vec![1001])
+            .with_kind(IndexKind::Udim);
+        let matcher = CollectionMatcher::new(&c).unwrap();
+        assert!(matcher.match_item("diffuse.1024.tx").is_some());
+        assert!(matcher.match_item("diffuse.1999.tx").is_some());
+        assert!(matcher.match_item("diffuse.1000.tx").is_none());
+        assert!(matcher.match_item("diffuse.2001.tx").is_none());
+        assert!(matcher.match_item("diffuse.-001.tx").is_none());
+    }
+}
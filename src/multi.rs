@@ -0,0 +1,630 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use strfmt::strfmt;
+
+use crate::collection::{self, IndexKind};
+use crate::natural;
+use crate::{AssembleOptions, Collection, CollectionOrder, Error, MatchSelection};
+
+// Head, separators, tail and paddings that items are grouped into
+// multi-dimensional collections by.
+type MultiCollectionKey = (String, Vec<String>, String, Vec<i32>);
+
+// Collection of items that vary on more than one number, such as the wedges
+// and frames of wedge_00.1001.bgeo, with an axis for each number. Separators
+// are the text between consecutive numbers, and each index has a number for
+// every axis, so wedge_03.1001.bgeo is index [3, 1001]. Indexes are always
+// sorted in ascending order without duplicates.
+#[derive(Debug, PartialEq)]
+pub struct MultiCollection {
+    pub(crate) head: String,
+    pub(crate) separators: Vec<String>,
+    pub(crate) tail: String,
+    pub(crate) paddings: Vec<i32>,
+    pub(crate) indexes: Vec<Vec<i64>>,
+}
+
+impl MultiCollection {
+    // Return a multi-dimensional collection of indexes, sorted with
+    // duplicates removed.
+    pub fn new(
+        head: String,
+        separators: Vec<String>,
+        tail: String,
+        paddings: Vec<i32>,
+        mut indexes: Vec<Vec<i64>>,
+    ) -> MultiCollection {
+        indexes.sort();
+        indexes.dedup();
+        MultiCollection {
+            head,
+            separators,
+            tail,
+            paddings,
+            indexes,
+        }
+    }
+
+    pub fn head(&self) -> &str {
+        &self.head
+    }
+
+    // Return the text between consecutive numbers, one fewer than the axes.
+    pub fn separators(&self) -> &[String] {
+        &self.separators
+    }
+
+    pub fn tail(&self) -> &str {
+        &self.tail
+    }
+
+    // Return the padding of each axis, or 0 for an axis that is not padded.
+    pub fn paddings(&self) -> &[i32] {
+        &self.paddings
+    }
+
+    // Return the indexes in ascending order, with a number for every axis.
+    pub fn indexes(&self) -> &[Vec<i64>] {
+        &self.indexes
+    }
+
+    // Return the number of axes.
+    pub fn axes(&self) -> usize {
+        self.paddings.len()
+    }
+
+    // Return the distinct indexes of axis in ascending order.
+    pub fn axis_indexes(&self, axis: usize) -> Vec<i64> {
+        let indexes: BTreeSet<i64> = self
+            .indexes
+            .iter()
+            .filter_map(|index| index.get(axis).copied())
+            .collect();
+        indexes.into_iter().collect()
+    }
+
+    // Return axis as a collection of its distinct indexes, with the other axes
+    // shown as their padding in its head and tail. For example the second axis
+    // of wedge_%02d.%04d.bgeo has head "wedge_%02d." and tail ".bgeo". Returns
+    // None if there is no such axis.
+    pub fn axis(&self, axis: usize) -> Option<Collection> {
+        let padding = *self.paddings.get(axis)?;
+        let mut head = self.head.to_string();
+        let mut tail = String::new();
+        for (other, separator) in self.separators.iter().enumerate() {
+            match other < axis {
+                true => {
+                    head.push_str(&padding_string(self.paddings[other]));
+                    head.push_str(separator);
+                }
+                false => {
+                    tail.push_str(separator);
+                    tail.push_str(&padding_string(self.paddings[other + 1]));
+                }
+            }
+        }
+        tail.push_str(&self.tail);
+        Some(Collection::new(
+            head,
+            tail,
+            padding,
+            self.axis_indexes(axis),
+        ))
+    }
+
+    // Return the indexes missing from the grid of every combination of the
+    // axis indexes, in ascending order.
+    pub fn holes(&self) -> Vec<Vec<i64>> {
+        let mut grid: Vec<Vec<i64>> = vec![vec![]];
+        for axis in 0..self.axes() {
+            let axis_indexes = self.axis_indexes(axis);
+            grid = grid
+                .iter()
+                .flat_map(|index| {
+                    axis_indexes.iter().map(move |axis_index| {
+                        let mut index = index.to_vec();
+                        index.push(*axis_index);
+                        index
+                    })
+                })
+                .collect();
+        }
+        grid.retain(|index| self.indexes.binary_search(index).is_err());
+        grid
+    }
+
+    // Return formatted string representing the collection. The keys are those
+    // of Collection::format, where {padding} is the padding of every axis with
+    // the separators between them and {ranges} the ranges of every axis, such
+    // as "[0-9]x[1001-1100]". The keys {padding_N}, {ranges_N} and {holes_N}
+    // are those of axis N alone, and {holes} the missing indexes, such as
+    // "3x1004, 3x1005". Returns an error if the format contains an unknown key
+    // or the collection is empty.
+    pub fn format<T: AsRef<str>>(&self, fmt: Option<T>) -> Result<String, Error> {
+        if self.indexes.is_empty() {
+            return Err(Error::EmptyCollection);
+        }
+        let fmt = match &fmt {
+            Some(fmt) => fmt.as_ref(),
+            None => "{head}{padding}{tail} {ranges}",
+        };
+
+        let mut vars = HashMap::new();
+        let mut padding = String::new();
+        let mut ranges = vec![];
+        for axis in 0..self.axes() {
+            let collection = self.axis(axis).ok_or(Error::EmptyCollection)?;
+            let axis_padding = padding_string(self.paddings[axis]);
+            let axis_ranges = collection.format(Some("{ranges}"))?;
+            if axis > 0 {
+                padding.push_str(&self.separators[axis - 1]);
+            }
+            padding.push_str(&axis_padding);
+            ranges.push(format!("[{}]", axis_ranges));
+            if fmt.contains(&format!("{{holes_{}}}", axis)) {
+                vars.insert(
+                    format!("holes_{}", axis),
+                    collection.format(Some("{holes}"))?,
+                );
+            }
+            vars.insert(format!("padding_{}", axis), axis_padding);
+            vars.insert(format!("ranges_{}", axis), axis_ranges);
+        }
+        if fmt.contains("{holes}") {
+            let holes: Vec<String> = self
+                .holes()
+                .iter()
+                .map(|index| {
+                    let index: Vec<String> = index.iter().map(|i| i.to_string()).collect();
+                    index.join("x")
+                })
+                .collect();
+            vars.insert("holes".to_string(), holes.join(", "));
+        }
+        vars.insert("head".to_string(), self.head.to_string());
+        vars.insert("tail".to_string(), self.tail.to_string());
+        vars.insert("padding".to_string(), padding);
+        vars.insert("ranges".to_string(), ranges.join("x"));
+
+        strfmt(fmt, &vars).map_err(|error| collection::format_error(fmt, error))
+    }
+
+    // Return the item of index, such as wedge_03.1001.bgeo for [3, 1001].
+    fn item(&self, index: &[i64]) -> String {
+        let mut item = self.head.to_string();
+        for (axis, axis_index) in index.iter().enumerate() {
+            if axis > 0 {
+                item.push_str(&self.separators[axis - 1]);
+            }
+            item.push_str(&collection::pad_index(
+                *axis_index,
+                self.paddings[axis],
+                IndexKind::Frame,
+            ));
+        }
+        item.push_str(&self.tail);
+        item
+    }
+}
+
+// Group items that vary on more than one number into multi-dimensional
+// collections. Each pattern captures the numbers in order as "index_0",
+// "index_1" and so on, with optional "padding_0", "padding_1" groups of
+// leading zeros, for example:
+//
+//     "_(?P<index_0>(?P<padding_0>0*)\\d+)\\.(?P<index_1>(?P<padding_1>0*)\\d+)\\."
+//
+// Items are grouped by their head, separators, tail and the padding of each
+// axis. Options apply as they do to assemble_with, except that unpadded
+// indexes are not merged, subframes and UDIM tiles are not recognised, and
+// a selection of MostVarying is not supported. Leftmost and Rightmost keep
+// the match whose first number is leftmost or rightmost. Returns an error
+// if a pattern is invalid or has no "index_0" group, or the selection is
+// MostVarying.
+pub fn assemble_multi<T: AsRef<str>>(
+    iterable: &[T],
+    patterns: Vec<String>,
+    options: &AssembleOptions,
+) -> Result<(Vec<MultiCollection>, Vec<String>), Error> {
+    if options.selection == MatchSelection::MostVarying {
+        return Err(Error::UnsupportedSelection(options.selection));
+    }
+    let compiled_patterns = crate::compile_patterns(&patterns, "index_0", options)?;
+    let mut collection_map: HashMap<MultiCollectionKey, Vec<Vec<i64>>> = HashMap::new();
+    let mut collection_keys: Vec<MultiCollectionKey> = vec![];
+    let mut originals: HashMap<MultiCollectionKey, MultiCollectionKey> = HashMap::new();
+    let mut memberships: Vec<Vec<(MultiCollectionKey, Vec<i64>)>> = vec![];
+
+    for item in iterable.iter() {
+        let mut item_captures = vec![];
+        for pattern in compiled_patterns.iter() {
+            let axes = (0..)
+                .take_while(|axis| {
+                    let name = format!("index_{}", axis);
+                    pattern.capture_names().any(|n| n == Some(name.as_str()))
+                })
+                .count();
+            for captures in pattern.captures_iter(item.as_ref()) {
+                if let Some(capture) = multi_capture(item.as_ref(), &captures, axes) {
+                    item_captures.push(capture);
+                }
+            }
+        }
+        let item_captures = match options.selection {
+            MatchSelection::Leftmost => item_captures
+                .into_iter()
+                .min_by_key(|capture| capture.0 .0.len())
+                .into_iter()
+                .collect(),
+            MatchSelection::Rightmost => item_captures
+                .into_iter()
+                .rev()
+                .max_by_key(|capture| capture.0 .0.len())
+                .into_iter()
+                .collect(),
+            MatchSelection::All | MatchSelection::MostVarying => item_captures,
+        };
+
+        let mut item_memberships = vec![];
+        for (original, index) in item_captures {
+            let key = match options.case_sensitive {
+                true => original.to_owned(),
+                false => (
+                    original.0.to_lowercase(),
+                    original.1.iter().map(|s| s.to_lowercase()).collect(),
+                    original.2.to_lowercase(),
+                    original.3.to_vec(),
+                ),
+            };
+            originals.entry(key.to_owned()).or_insert(original);
+            match collection_map.get_mut(&key) {
+                Some(indexes) => indexes.push(index.to_vec()),
+                None => {
+                    collection_keys.push(key.to_owned());
+                    collection_map.insert(key.to_owned(), vec![index.to_vec()]);
+                }
+            }
+            item_memberships.push((key, index));
+        }
+        memberships.push(item_memberships);
+    }
+
+    let mut collections: Vec<(&MultiCollectionKey, MultiCollection)> = vec![];
+    for key in collection_keys.iter() {
+        let (head, separators, tail, paddings) = &originals[key];
+        let collection = MultiCollection::new(
+            head.to_string(),
+            separators.to_vec(),
+            tail.to_string(),
+            paddings.to_vec(),
+            collection_map[key].to_vec(),
+        );
+        if collection.indexes.len() >= options.minimum_items {
+            collections.push((key, collection));
+        }
+    }
+
+    let collection_map: HashMap<&MultiCollectionKey, &MultiCollection> = collections
+        .iter()
+        .map(|(key, collection)| (*key, collection))
+        .collect();
+    let mut remainder: Vec<String> = Vec::new();
+    for (item, item_memberships) in iterable.iter().zip(memberships.iter()) {
+        if !item_memberships.iter().any(|(key, index)| {
+            collection_map
+                .get(key)
+                .is_some_and(|collection| collection.indexes.binary_search(index).is_ok())
+        }) {
+            remainder.push(item.as_ref().to_string());
+        }
+    }
+
+    let mut collections: Vec<MultiCollection> = collections
+        .into_iter()
+        .map(|(_, collection)| collection)
+        .collect();
+
+    // Set padding for ambiguous axes according to the
+    // assume_padded_when_ambiguous setting.
+    if options.assume_padded_when_ambiguous {
+        for collection in collections.iter_mut() {
+            for axis in 0..collection.axes() {
+                let indexes = collection.axis_indexes(axis);
+                if let (0, Some(first), Some(last)) =
+                    (collection.paddings[axis], indexes.first(), indexes.last())
+                {
+                    if collection::index_width(*first) == collection::index_width(*last) {
+                        collection.paddings[axis] = collection::index_width(*first);
+                    }
+                }
+            }
+        }
+    }
+
+    if options.order == CollectionOrder::Natural {
+        collections.sort_by(natural_multi_collection_cmp);
+        remainder.sort_by(|a, b| natural::natural_cmp(a, b));
+    }
+    Ok((collections, remainder))
+}
+
+// Return the head, separators, tail and paddings of item and its index, from
+// captures of axes index groups. Returns None if an index group did not
+// match, is not after the previous one or is not a number.
+fn multi_capture(
+    item: &str,
+    captures: &regex::Captures,
+    axes: usize,
+) -> Option<(MultiCollectionKey, Vec<i64>)> {
+    let mut head = None;
+    let mut separators = vec![];
+    let mut paddings = vec![];
+    let mut index = vec![];
+    let mut end = 0;
+    for axis in 0..axes {
+        let index_match = captures.name(&format!("index_{}", axis))?;
+        if index_match.start() < end {
+            return None;
+        }
+        match head {
+            None => head = Some(&item[..index_match.start()]),
+            Some(_) => separators.push(item[end..index_match.start()].to_string()),
+        }
+        let padding = match captures.name(&format!("padding_{}", axis)) {
+            Some(padding) if !padding.as_str().is_empty() => {
+                index_match.as_str().trim_start_matches('-').chars().count() as i32
+            }
+            _ => 0,
+        };
+        paddings.push(padding);
+        index.push(index_match.as_str().parse::<i64>().ok()?);
+        end = index_match.end();
+    }
+    Some((
+        (
+            head?.to_string(),
+            separators,
+            item[end..].to_string(),
+            paddings,
+        ),
+        index,
+    ))
+}
+
+// Compare multi-dimensional collections in natural order of head,
+// separators, tail and paddings, then by their first index.
+fn natural_multi_collection_cmp(a: &MultiCollection, b: &MultiCollection) -> Ordering {
+    natural::natural_cmp(&a.head, &b.head)
+        .then_with(|| {
+            a.separators
+                .iter()
+                .zip(b.separators.iter())
+                .map(|(a, b)| natural::natural_cmp(a, b))
+                .find(|ordering| *ordering != Ordering::Equal)
+                .unwrap_or_else(|| a.separators.len().cmp(&b.separators.len()))
+        })
+        .then_with(|| natural::natural_cmp(&a.tail, &b.tail))
+        .then_with(|| a.paddings.cmp(&b.paddings))
+        .then_with(|| a.indexes.first().cmp(&b.indexes.first()))
+}
+
+// Return the printf style padding of an axis, such as %04d.
+fn padding_string(padding: i32) -> String {
+    match padding {
+        0 => "%d".to_string(),
+        padding => format!("%0{}d", padding),
+    }
+}
+
+// Non consuming iterator over the items of a multi-dimensional collection.
+pub struct MultiIterHelper<'a> {
+    iter: std::slice::Iter<'a, Vec<i64>>,
+    collection: &'a MultiCollection,
+}
+
+impl<'a> IntoIterator for &'a MultiCollection {
+    type Item = String;
+    type IntoIter = MultiIterHelper<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        MultiIterHelper {
+            iter: self.indexes.iter(),
+            collection: self,
+        }
+    }
+}
+
+impl<'a> Iterator for MultiIterHelper<'a> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|index| self.collection.item(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wedges() -> MultiCollection {
+        MultiCollection::new(
+            "wedge_".to_string(),
+            vec![".".to_string()],
+            ".bgeo".to_string(),
+            vec![2, 4],
+            vec![
+                vec![0, 1001],
+                vec![0, 1002],
+                vec![1, 1001],
+                vec![1, 1002],
+                vec![2, 1001],
+            ],
+        )
+    }
+
+    #[test]
+    fn test_format() {
+        let c = wedges();
+        assert_eq!(
+            c.format::<&str>(None).unwrap(),
+            "wedge_%02d.%04d.bgeo [0-2]x[1001-1002]"
+        );
+        assert_eq!(
+            c.format(Some("{head}{padding_0}{tail} [{ranges_0}] [{holes}]"))
+                .unwrap(),
+            "wedge_%02d.bgeo [0-2] [2x1002]"
+        );
+        assert!(matches!(
+            c.format(Some("{padding_2}")),
+            Err(Error::UnknownFormatKey(key)) if key == "padding_2"
+        ));
+
+        let c = MultiCollection::new(String::new(), vec![], String::new(), vec![4], vec![]);
+        assert!(matches!(
+            c.format::<&str>(None),
+            Err(Error::EmptyCollection)
+        ));
+    }
+
+    #[test]
+    fn test_axis() {
+        let c = wedges();
+        assert_eq!(c.axes(), 2);
+        assert_eq!(c.axis_indexes(0), vec![0, 1, 2]);
+        assert_eq!(
+            c.axis(1).unwrap(),
+            Collection::new(
+                "wedge_%02d.".to_string(),
+                ".bgeo".to_string(),
+                4,
+                vec![1001, 1002]
+            )
+        );
+        assert_eq!(c.axis(0).unwrap().tail, ".%04d.bgeo");
+        assert!(c.axis(2).is_none());
+    }
+
+    #[test]
+    fn test_holes() {
+        let c = wedges();
+        assert_eq!(c.holes(), vec![vec![2, 1002]]);
+
+        let mut indexes = wedges().indexes;
+        indexes.push(vec![2, 1002]);
+        let c = MultiCollection::new(
+            "wedge_".to_string(),
+            vec![".".to_string()],
+            ".bgeo".to_string(),
+            vec![2, 4],
+            indexes,
+        );
+        assert!(c.holes().is_empty());
+
+        // Indexes are sorted and deduplicated, so holes are found in any
+        // order of input.
+        let c = MultiCollection::new(
+            "wedge_".to_string(),
+            vec![".".to_string()],
+            ".bgeo".to_string(),
+            vec![1, 4],
+            vec![vec![1, 1002], vec![0, 1001], vec![1, 1001], vec![1, 1001]],
+        );
+        assert_eq!(c.indexes(), &[vec![0, 1001], vec![1, 1001], vec![1, 1002]]);
+        assert_eq!(c.holes(), vec![vec![0, 1002]]);
+    }
+
+    #[test]
+    fn test_assemble_multi() {
+        let mut files = vec![];
+        for wedge in 0..3 {
+            for frame in 1001..1004 {
+                files.push(format!("cache/wedge_{:02}.{}.bgeo", wedge, frame));
+            }
+        }
+        files.retain(|file| file != "cache/wedge_02.1003.bgeo");
+        files.push("cache/wedge_x.1001.bgeo".to_string());
+        files.push("cache/wedge_9.1001.bgeo".to_string());
+
+        let patterns = vec![
+            "_(?P<index_0>(?P<padding_0>0*)\\d+)\\.(?P<index_1>(?P<padding_1>0*)\\d+)\\."
+                .to_string(),
+        ];
+        let (collections, remainder) =
+            assemble_multi(&files, patterns, &AssembleOptions::new()).unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(
+            remainder,
+            vec!["cache/wedge_9.1001.bgeo", "cache/wedge_x.1001.bgeo"]
+        );
+
+        let c = &collections[0];
+        assert_eq!(c.head, "cache/wedge_");
+        assert_eq!(c.separators, vec!["."]);
+        assert_eq!(c.tail, ".bgeo");
+        assert_eq!(c.paddings, vec![2, 4]);
+        assert_eq!(c.indexes.len(), 8);
+        assert_eq!(c.holes(), vec![vec![2, 1003]]);
+        assert_eq!(
+            c.format::<&str>(None).unwrap(),
+            "cache/wedge_%02d.%04d.bgeo [0-2]x[1001-1003]"
+        );
+        let members: Vec<String> = c.into_iter().collect();
+        assert_eq!(members, files[..8]);
+
+        assert!(matches!(
+            assemble_multi(&files, vec!["(?P<index>\\d+)".to_string()], &AssembleOptions::new()),
+            Err(Error::MissingGroup { group, .. }) if group == "index_0"
+        ));
+    }
+
+    #[test]
+    fn test_assemble_multi_selection() {
+        let mut files = vec![];
+        for a in 1..3 {
+            for b in 1..3 {
+                files.push(format!("s_{}_{}_1.txt", a, b));
+            }
+        }
+        // The first pattern matches the first two numbers, and the second the
+        // last two.
+        let patterns = [
+            "_(?P<index_0>\\d+)_(?P<index_1>\\d+)_".to_string(),
+            "_(?P<index_0>\\d+)_(?P<index_1>\\d+)\\.".to_string(),
+        ];
+        let heads = |selection: MatchSelection| -> Result<Vec<String>, Error> {
+            let options = AssembleOptions::new().minimum_items(2).selection(selection);
+            let (collections, remainder) = assemble_multi(&files, patterns.to_vec(), &options)?;
+            assert!(remainder.is_empty());
+            Ok(collections.iter().map(|c| c.head().to_string()).collect())
+        };
+        assert_eq!(
+            heads(MatchSelection::All).unwrap(),
+            vec!["s_", "s_1_", "s_2_"]
+        );
+        assert_eq!(heads(MatchSelection::Leftmost).unwrap(), vec!["s_"]);
+        assert_eq!(
+            heads(MatchSelection::Rightmost).unwrap(),
+            vec!["s_1_", "s_2_"]
+        );
+        assert!(matches!(
+            heads(MatchSelection::MostVarying),
+            Err(Error::UnsupportedSelection(MatchSelection::MostVarying))
+        ));
+    }
+
+    #[test]
+    fn test_iterator() {
+        let c = wedges();
+        let members: Vec<String> = c.into_iter().collect();
+        assert_eq!(
+            members,
+            vec![
+                "wedge_00.1001.bgeo",
+                "wedge_00.1002.bgeo",
+                "wedge_01.1001.bgeo",
+                "wedge_01.1002.bgeo",
+                "wedge_02.1001.bgeo",
+            ]
+        );
+    }
+}
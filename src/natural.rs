@@ -0,0 +1,75 @@
+use std::cmp::Ordering;
+
+// Compare strings in natural order, so runs of digits are compared by their
+// numeric value and "render2" sorts before "render10". Strings that only differ
+// by leading zeros are ordered by their length to keep the order total.
+pub(crate) fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chunks = Chunks(a);
+    let mut b_chunks = Chunks(b);
+    loop {
+        match (a_chunks.next(), b_chunks.next()) {
+            (None, None) => return a.len().cmp(&b.len()).then_with(|| a.cmp(b)),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_chunk), Some(b_chunk)) => {
+                let ordering = match (is_digits(a_chunk), is_digits(b_chunk)) {
+                    (true, true) => {
+                        let a_digits = a_chunk.trim_start_matches('0');
+                        let b_digits = b_chunk.trim_start_matches('0');
+                        a_digits
+                            .len()
+                            .cmp(&b_digits.len())
+                            .then_with(|| a_digits.cmp(b_digits))
+                    }
+                    _ => a_chunk.cmp(b_chunk),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+fn is_digits(chunk: &str) -> bool {
+    chunk.starts_with(|c: char| c.is_ascii_digit())
+}
+
+// Iterator over alternating runs of ascii digits and other characters.
+struct Chunks<'a>(&'a str);
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let digits = is_digits(self.0);
+        let end = self
+            .0
+            .find(|c: char| c.is_ascii_digit() != digits)
+            .unwrap_or(self.0.len());
+        if end == 0 {
+            return None;
+        }
+        let (chunk, rest) = self.0.split_at(end);
+        self.0 = rest;
+        Some(chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(natural_cmp("render2", "render10"), Ordering::Less);
+        assert_eq!(natural_cmp("render10", "render2"), Ordering::Greater);
+        assert_eq!(natural_cmp("render.0010", "render.10"), Ordering::Greater);
+        assert_eq!(natural_cmp("a", "a.exr"), Ordering::Less);
+        assert_eq!(natural_cmp("v001/a", "v001/a"), Ordering::Equal);
+
+        let mut items = vec!["b10", "a2", "b9", "a10", "a1"];
+        items.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(items, vec!["a1", "a2", "a10", "b9", "b10"]);
+    }
+}
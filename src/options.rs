@@ -0,0 +1,133 @@
+// Options controlling how assemble_with groups items into collections.
+//
+// The defaults reproduce the behaviour of assemble, so options only need to
+// be set where that behaviour should change:
+//
+//     let options = AssembleOptions::new().minimum_items(2);
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AssembleOptions {
+    pub(crate) minimum_items: usize,
+    pub(crate) assume_padded_when_ambiguous: bool,
+    pub(crate) case_sensitive: bool,
+    pub(crate) merge_unpadded: bool,
+    pub(crate) order: CollectionOrder,
+    pub(crate) subframes: bool,
+    pub(crate) udim: bool,
+    pub(crate) selection: MatchSelection,
+}
+
+// Order of the collections and remainder returned by assemble_with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CollectionOrder {
+    // Collections in natural order of head, tail and padding, then kind of
+    // index and first index. The remainder in natural order.
+    Natural,
+    // Collections in the order their first item appeared in the input. The
+    // remainder in input order.
+    FirstAppearance,
+}
+
+// Which numbers of an item, such as 001 and 1001 of main_v001/render.1001.exr,
+// it is grouped by when the patterns match more than one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum MatchSelection {
+    // Every number, so the item is in a collection for each.
+    All,
+    // The first number in the item.
+    Leftmost,
+    // The last number in the item.
+    Rightmost,
+    // The number with the most distinct values across items that differ
+    // only by their numbers, chosen once for all of those items so that each
+    // is in exactly one collection. Ties are broken by the last number in
+    // the item.
+    MostVarying,
+}
+
+impl Default for AssembleOptions {
+    fn default() -> Self {
+        AssembleOptions {
+            minimum_items: 3,
+            assume_padded_when_ambiguous: true,
+            case_sensitive: true,
+            merge_unpadded: true,
+            order: CollectionOrder::Natural,
+            subframes: false,
+            udim: false,
+            selection: MatchSelection::All,
+        }
+    }
+}
+
+impl AssembleOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Minimum number of indexes a collection must have to be returned,
+    // smaller collections have their members added to the remainder.
+    pub fn minimum_items(mut self, minimum_items: usize) -> Self {
+        self.minimum_items = minimum_items;
+        self
+    }
+
+    // Whether an unpadded collection whose first and last indexes have the
+    // same width, such as 1001-1005, is given that width as its padding.
+    pub fn assume_padded_when_ambiguous(mut self, assume_padded: bool) -> Self {
+        self.assume_padded_when_ambiguous = assume_padded;
+        self
+    }
+
+    // Whether heads and tails that differ only in case form separate
+    // collections. Otherwise a collection takes the head and tail of its first
+    // item, which Collection::contains compares by exact case.
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    // Whether indexes of unpadded collections are merged into a padded
+    // collection with the same head and tail when their width matches its
+    // padding, for example 0998-0999 and 1000-1001 into 0998-1001.
+    pub fn merge_unpadded(mut self, merge_unpadded: bool) -> Self {
+        self.merge_unpadded = merge_unpadded;
+        self
+    }
+
+    // Order of the returned collections and remainder.
+    pub fn order(mut self, order: CollectionOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    // Whether the default pattern treats the last number after a dot that is
+    // followed by a dot and more digits before the extensions as a subframe,
+    // such as 1001.25 in sim.1001.25.bgeo, but not v001.1001 in
+    // comp_v001.1001.exr. Custom patterns can capture subframes with a
+    // "subframe" group after the index.
+    pub fn subframes(mut self, subframes: bool) -> Self {
+        self.subframes = subframes;
+        self
+    }
+
+    // Whether items are grouped as UDIM texture tiles, such as 1001-1024 in
+    // diffuse.1001.tx. Only four digit numbers from 1001 to 1999 are grouped,
+    // and other numbers are ignored.
+    pub fn udim(mut self, udim: bool) -> Self {
+        self.udim = udim;
+        self
+    }
+
+    // Which numbers items are grouped by when the patterns match more than
+    // one.
+    pub fn selection(mut self, selection: MatchSelection) -> Self {
+        self.selection = selection;
+        self
+    }
+}
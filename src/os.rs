@@ -0,0 +1,121 @@
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+
+// Names that are not valid UTF-8 are matched and grouped as strings with each
+// byte that is not part of a valid character escaped as a private use
+// character from U+10FF00 to U+10FFFF, so 0xE9 is U+10FFE9. Characters in that
+// range that are really in the name are escaped as each of their bytes, so
+// every name converts back to exactly the bytes it was.
+const ESCAPE_START: u32 = 0x10FF00;
+
+// Return name as a string, escaping any bytes that are not valid UTF-8. Names
+// that are valid UTF-8 and have no characters to escape are borrowed.
+pub(crate) fn encode(name: &OsStr) -> Cow<'_, str> {
+    match name.to_str() {
+        Some(name) if !name.chars().any(is_escape) => Cow::Borrowed(name),
+        _ => Cow::Owned(encode_bytes(&bytes(name))),
+    }
+}
+
+// Return the name a string returned by encode was made from.
+pub(crate) fn decode(name: &str) -> OsString {
+    if !name.chars().any(is_escape) {
+        return OsString::from(name);
+    }
+    let mut bytes = vec![];
+    for c in name.chars() {
+        match is_escape(c) {
+            true => bytes.push((c as u32 - ESCAPE_START) as u8),
+            false => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    from_bytes(bytes)
+}
+
+fn is_escape(c: char) -> bool {
+    c as u32 >= ESCAPE_START
+}
+
+fn escape(byte: u8) -> char {
+    char::from_u32(ESCAPE_START + byte as u32).unwrap()
+}
+
+fn encode_bytes(mut bytes: &[u8]) -> String {
+    let mut name = String::new();
+    while !bytes.is_empty() {
+        let (valid, invalid) = match std::str::from_utf8(bytes) {
+            Ok(valid) => (valid, &[][..]),
+            Err(error) => {
+                let (valid, rest) = bytes.split_at(error.valid_up_to());
+                let invalid = error.error_len().unwrap_or(rest.len());
+                (std::str::from_utf8(valid).unwrap(), &rest[..invalid])
+            }
+        };
+        for c in valid.chars() {
+            match is_escape(c) {
+                true => name.extend(c.encode_utf8(&mut [0; 4]).bytes().map(escape)),
+                false => name.push(c),
+            }
+        }
+        name.extend(invalid.iter().copied().map(escape));
+        bytes = &bytes[valid.len() + invalid.len()..];
+    }
+    name
+}
+
+#[cfg(unix)]
+fn bytes(name: &OsStr) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(name.as_bytes())
+}
+
+#[cfg(unix)]
+fn from_bytes(bytes: Vec<u8>) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(bytes)
+}
+
+// Other platforms do not expose the bytes of names, so names that are not
+// valid unicode are converted lossily.
+#[cfg(not(unix))]
+fn bytes(name: &OsStr) -> Cow<'_, [u8]> {
+    match name.to_string_lossy() {
+        Cow::Borrowed(name) => Cow::Borrowed(name.as_bytes()),
+        Cow::Owned(name) => Cow::Owned(name.into_bytes()),
+    }
+}
+
+#[cfg(not(unix))]
+fn from_bytes(bytes: Vec<u8>) -> OsString {
+    OsString::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_utf8() {
+        assert!(matches!(
+            encode(OsStr::new("render.1001.exr")),
+            Cow::Borrowed("render.1001.exr")
+        ));
+        assert_eq!(decode("render.1001.exr"), OsStr::new("render.1001.exr"));
+
+        let name = "caf\u{e9}.\u{10FFE9}.exr";
+        assert_eq!(encode(OsStr::new(name)).chars().count(), 13);
+        assert_eq!(decode(&encode(OsStr::new(name))), OsStr::new(name));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_encode_bytes() {
+        let name = from_bytes(b"caf\xe9.1001.exr".to_vec());
+        let encoded = encode(&name);
+        assert_eq!(encoded, "caf\u{10FFE9}.1001.exr");
+        assert_eq!(decode(&encoded), name);
+
+        let name = from_bytes(b"\xff\xfe\xf4\x8f".to_vec());
+        assert_eq!(decode(&encode(&name)), name);
+    }
+}
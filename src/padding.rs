@@ -0,0 +1,490 @@
+use regex::Captures;
+
+use crate::udim;
+use crate::{Error, FrameRange, IndexKind};
+
+// Notation for the padding written in place of the index of a collection, as
+// different applications expect it. Collection::format_with writes {padding}
+// in a style, and each style has a format key of its own, such as {hash}.
+// parse and CollectionMatcher accept every style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum PaddingStyle {
+    // printf style, such as %04d, or %d when unpadded. This is the default.
+    #[default]
+    Printf,
+    // A hash per digit, such as ####, or # when unpadded.
+    Hash,
+    // An at-sign per digit, such as @@@@, or @ when unpadded.
+    #[cfg_attr(feature = "serde", serde(rename = "at"))]
+    AtSign,
+    // Houdini's frame variable, such as $F4, or $F when unpadded. Houdini has
+    // no variable for subframes with padding.
+    Houdini,
+    // Nuke reads both printf and hash padding. Padded collections are written
+    // with a hash per digit, such as ####, and unpadded ones as %d.
+    Nuke,
+    // RV's frame range followed by # for four digits or an at-sign per digit
+    // otherwise, such as 1001-1100# or 1-100@@@. The range is always written
+    // as a start and end, and RV has no notation for subframes.
+    Rv,
+    // A hash per digit, such as ####, as Blender replaces in output paths.
+    // Blender adds no separator before the frame, so the dot at the end of
+    // the head is dropped before the token, as in render####.png.
+    Blender,
+    // A Python format field, such as {frame:04d}, or {frame} when unpadded.
+    // Subframes are written as floats, such as {frame:07.2f}.
+    Python,
+}
+
+// Padding read from a token by parse.
+#[derive(Debug, Default)]
+pub(crate) struct Token {
+    pub(crate) padding: i32,
+    pub(crate) subframe: Option<i32>,
+    // The frames of an RV token.
+    pub(crate) frames: Option<FrameRange>,
+}
+
+impl PaddingStyle {
+    pub(crate) const ALL: [PaddingStyle; 8] = [
+        PaddingStyle::Printf,
+        PaddingStyle::Hash,
+        PaddingStyle::AtSign,
+        PaddingStyle::Houdini,
+        PaddingStyle::Nuke,
+        PaddingStyle::Rv,
+        PaddingStyle::Blender,
+        PaddingStyle::Python,
+    ];
+
+    // Styles tried in turn when parsing {padding}. RV comes before hash, as
+    // a hash pattern would otherwise read the RV range as part of the head.
+    const PARSE_ORDER: [PaddingStyle; 6] = [
+        PaddingStyle::Printf,
+        PaddingStyle::Rv,
+        PaddingStyle::Hash,
+        PaddingStyle::AtSign,
+        PaddingStyle::Houdini,
+        PaddingStyle::Python,
+    ];
+
+    // Return the format key of the style, such as "hash" for {hash}, which
+    // is also its name on the command line.
+    pub fn key(&self) -> &'static str {
+        match self {
+            PaddingStyle::Printf => "printf",
+            PaddingStyle::Hash => "hash",
+            PaddingStyle::AtSign => "at",
+            PaddingStyle::Houdini => "houdini",
+            PaddingStyle::Nuke => "nuke",
+            PaddingStyle::Rv => "rv",
+            PaddingStyle::Blender => "blender",
+            PaddingStyle::Python => "python",
+        }
+    }
+
+    // Return the style with format key, if there is one.
+    pub(crate) fn from_key(key: &str) -> Option<PaddingStyle> {
+        PaddingStyle::ALL
+            .iter()
+            .copied()
+            .find(|style| style.key() == key)
+    }
+
+    // Return the styles parse tries for a format key, or None if key is not
+    // a padding key.
+    pub(crate) fn parse_styles(key: &str) -> Option<&'static [PaddingStyle]> {
+        if key == "padding" {
+            return Some(&PaddingStyle::PARSE_ORDER);
+        }
+        Some(match PaddingStyle::from_key(key)? {
+            PaddingStyle::Printf => &[PaddingStyle::Printf],
+            PaddingStyle::Hash => &[PaddingStyle::Hash],
+            PaddingStyle::Blender => &[PaddingStyle::Blender],
+            PaddingStyle::AtSign => &[PaddingStyle::AtSign],
+            PaddingStyle::Houdini => &[PaddingStyle::Houdini],
+            PaddingStyle::Nuke => &[PaddingStyle::Printf, PaddingStyle::Hash],
+            PaddingStyle::Rv => &[PaddingStyle::Rv],
+            PaddingStyle::Python => &[PaddingStyle::Python],
+        })
+    }
+
+    // Return the token for padding and indexes of kind, such as %04d, with
+    // range written before RV tokens. Returns an error for subframes in a
+    // style that cannot write them.
+    pub(crate) fn token(
+        &self,
+        padding: i32,
+        kind: IndexKind,
+        range: &str,
+    ) -> Result<String, Error> {
+        let subframe = match kind {
+            IndexKind::Udim => return Ok(udim::UDIM_TOKEN.to_string()),
+            IndexKind::Frame => None,
+            IndexKind::Subframe { padding } => Some(padding),
+        };
+        let padded = padding > 0;
+        Ok(match (self, subframe) {
+            (PaddingStyle::Houdini, Some(_)) | (PaddingStyle::Rv, Some(_)) => {
+                return Err(Error::UnsupportedPaddingStyle(*self))
+            }
+            (PaddingStyle::Python, Some(subframe)) => match padding {
+                0 => format!("{{frame:.{}f}}", subframe),
+                padding => format!("{{frame:0{}.{}f}}", padding + subframe + 1, subframe),
+            },
+            (PaddingStyle::Rv, None) => format!("{}{}", range, self.digits(padding, padded)),
+            (_, None) => self.digits(padding, padded),
+            (_, Some(subframe)) => format!(
+                "{}.{}",
+                self.digits(padding, padded),
+                self.digits(subframe, padded)
+            ),
+        })
+    }
+
+    // Return the token for a number of digits, or for an unpadded number if
+    // digits is 0. Nuke writes hashes for padded collections.
+    fn digits(&self, digits: i32, padded: bool) -> String {
+        let repeat = |c: &str| c.repeat(digits.max(1) as usize);
+        match (self, digits) {
+            (PaddingStyle::Nuke, _) if padded => repeat("#"),
+            (PaddingStyle::Printf, 0) | (PaddingStyle::Nuke, 0) => "%d".to_string(),
+            (PaddingStyle::Printf, digits) | (PaddingStyle::Nuke, digits) => {
+                format!("%0{}d", digits)
+            }
+            (PaddingStyle::Hash, _) | (PaddingStyle::Blender, _) => repeat("#"),
+            (PaddingStyle::AtSign, _) => repeat("@"),
+            (PaddingStyle::Houdini, 0) => "$F".to_string(),
+            (PaddingStyle::Houdini, digits) => format!("$F{}", digits),
+            (PaddingStyle::Rv, 4) => "#".to_string(),
+            (PaddingStyle::Rv, _) => repeat("@"),
+            (PaddingStyle::Python, 0) => "{frame}".to_string(),
+            (PaddingStyle::Python, digits) => format!("{{frame:0{}d}}", digits),
+        }
+    }
+
+    // Return the regex matching tokens of the style for parse, with groups
+    // "padding", "subframe" and "frames" read by parse_token, and "udim" for
+    // the UDIM token.
+    pub(crate) fn pattern(&self) -> String {
+        let pattern = match self {
+            PaddingStyle::Printf | PaddingStyle::Nuke => {
+                "%(?P<padding>\\d*)d(?:\\.%0(?P<subframe>\\d+)d)?"
+            }
+            PaddingStyle::Hash | PaddingStyle::Blender => "(?P<padding>#+)(?:\\.(?P<subframe>#+))?",
+            PaddingStyle::AtSign => "(?P<padding>@+)(?:\\.(?P<subframe>@+))?",
+            PaddingStyle::Houdini => "\\$F(?P<padding>\\d*)",
+            PaddingStyle::Rv => "(?P<frames>-?\\d+--?\\d+(?:x\\d+)?)(?P<padding>#|@+)",
+            PaddingStyle::Python => {
+                "\\{frame(?::(?P<padding>\\d*)d|:(?P<width>\\d*)\\.(?P<subframe>\\d+)f)?\\}"
+            }
+        };
+        format!("(?:{}|(?P<udim>{}))", pattern, udim::UDIM_TOKEN)
+    }
+
+    // Return the regex matching tokens of the style without its named
+    // groups, for padding keys after the first, whose tokens parse does not
+    // read.
+    pub(crate) fn unnamed_pattern(&self) -> String {
+        let pattern = self.pattern();
+        let mut unnamed = String::new();
+        let mut remaining = pattern.as_str();
+        while let Some(open) = remaining.find("(?P<") {
+            let close = open + remaining[open..].find('>').unwrap_or(0);
+            unnamed.push_str(&remaining[..open]);
+            unnamed.push_str("(?:");
+            remaining = &remaining[close + 1..];
+        }
+        unnamed.push_str(remaining);
+        unnamed
+    }
+
+    // Return the regex matching the tail after a token for parse. Hash and
+    // at-sign tails hold no hash or at-sign, so the token matched is the last
+    // run of them, such as #### rather than # in "take#2/render.####.exr".
+    pub(crate) fn tail_pattern(&self) -> &'static str {
+        match self {
+            PaddingStyle::Hash | PaddingStyle::Blender => "[^#]*",
+            PaddingStyle::AtSign => "[^@]*",
+            _ => ".*",
+        }
+    }
+
+    // Return the padding of a token matched by pattern, or None if it is not
+    // a valid token. A single hash or at-sign is padding 1, as it is written
+    // for padding 1, and parse reads it as unpadded when indexes are wider.
+    pub(crate) fn parse_token(&self, captures: &Captures) -> Option<Token> {
+        let group = |name: &str| captures.name(name).map(|m| m.as_str());
+        let number = |value: &str| match value {
+            "" => Some(0),
+            value => value.parse::<i32>().ok(),
+        };
+        let count = |value: &str| value.len() as i32;
+        let mut token = Token::default();
+        match self {
+            PaddingStyle::Printf | PaddingStyle::Nuke | PaddingStyle::Houdini => {
+                token.padding = group("padding").map_or(Some(0), number)?;
+                token.subframe = match group("subframe") {
+                    Some(subframe) => Some(number(subframe)?),
+                    None => None,
+                };
+            }
+            PaddingStyle::Hash | PaddingStyle::Blender | PaddingStyle::AtSign => {
+                token.padding = group("padding").map_or(0, count);
+                token.subframe = group("subframe").map(|subframe| subframe.len() as i32);
+            }
+            PaddingStyle::Rv => {
+                token.padding = match group("padding") {
+                    Some("#") => 4,
+                    padding => padding.map_or(0, count),
+                };
+                token.frames = match group("frames") {
+                    Some(frames) => Some(frames.parse().ok()?),
+                    None => None,
+                };
+            }
+            PaddingStyle::Python => match (group("width"), group("subframe")) {
+                (Some(width), Some(subframe)) => {
+                    let subframe = number(subframe)?;
+                    token.padding = match number(width)? {
+                        0 => 0,
+                        width => (width - subframe - 1).max(0),
+                    };
+                    token.subframe = Some(subframe);
+                }
+                _ => token.padding = group("padding").map_or(Some(0), number)?,
+            },
+        }
+        Some(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Collection;
+
+    #[test]
+    fn test_styles() {
+        let c = Collection::new(
+            "render.".to_string(),
+            ".exr".to_string(),
+            4,
+            vec![1001, 1002, 1003, 1005],
+        );
+        let tokens: Vec<String> = PaddingStyle::ALL
+            .iter()
+            .map(|style| c.format_with(Some("{padding}"), *style).unwrap())
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                "%04d",
+                "####",
+                "@@@@",
+                "$F4",
+                "####",
+                "1001-1005#",
+                "####",
+                "{frame:04d}"
+            ]
+        );
+        assert_eq!(
+            c.format(Some("{head}{houdini}{tail} {nuke} {rv}")).unwrap(),
+            "render.$F4.exr #### 1001-1005#"
+        );
+        assert_eq!(
+            c.format_with::<&str>(None, PaddingStyle::Hash).unwrap(),
+            "render.####.exr [1001-1003, 1005]"
+        );
+
+        let c = Collection::new("render.".to_string(), ".exr".to_string(), 0, vec![1, 3, 5]);
+        let tokens: Vec<String> = PaddingStyle::ALL
+            .iter()
+            .map(|style| c.format_with(Some("{padding}"), *style).unwrap())
+            .collect();
+        assert_eq!(
+            tokens,
+            vec!["%d", "#", "@", "$F", "%d", "1-5x2@", "#", "{frame}"]
+        );
+
+        let c = Collection::new("sim.".to_string(), ".bgeo".to_string(), 4, vec![100100])
+            .with_kind(IndexKind::Subframe { padding: 2 });
+        assert_eq!(
+            c.format(Some("{hash} {at} {python}")).unwrap(),
+            "####.## @@@@.@@ {frame:07.2f}"
+        );
+        assert!(matches!(
+            c.format(Some("{houdini}")),
+            Err(Error::UnsupportedPaddingStyle(PaddingStyle::Houdini))
+        ));
+    }
+
+    #[test]
+    fn test_parse_styles() {
+        let parse = |value: &str| crate::parse(value, Some("{head}{padding}{tail}")).unwrap();
+        for value in [
+            "render.%04d.exr",
+            "render.####.exr",
+            "render.@@@@.exr",
+            "render.$F4.exr",
+            "render.{frame:04d}.exr",
+        ] {
+            let c = parse(value);
+            assert_eq!((c.head(), c.tail(), c.padding()), ("render.", ".exr", 4));
+        }
+        assert_eq!(parse("render.#.exr").padding(), 1);
+        let c = crate::parse("render.#.exr [9-10]", None::<&str>).unwrap();
+        assert_eq!((c.padding(), c.indexes()), (0, vec![9, 10]));
+        assert_eq!(
+            parse("user@host/render.%04d.exr").head(),
+            "user@host/render."
+        );
+
+        let c = parse("render.101-110x3@@@.exr");
+        assert_eq!((c.head(), c.padding()), ("render.", 3));
+        assert_eq!(c.indexes(), &[101, 104, 107, 110]);
+        assert_eq!(parse("render2####.png").head(), "render2");
+
+        let c = crate::parse("take#2/render.####.exr [1-3]", None::<&str>).unwrap();
+        assert_eq!(
+            (c.head(), c.tail(), c.padding()),
+            ("take#2/render.", ".exr", 4)
+        );
+        assert_eq!(c.indexes(), &[1, 2, 3]);
+        let c = parse("a@b/render.@@@@.exr");
+        assert_eq!(
+            (c.head(), c.tail(), c.padding()),
+            ("a@b/render.", ".exr", 4)
+        );
+        let c = parse("take#2/sim.####.##.bgeo");
+        assert_eq!(
+            (c.head(), c.tail(), c.padding()),
+            ("take#2/sim.", ".bgeo", 4)
+        );
+        assert_eq!(c.kind(), IndexKind::Subframe { padding: 2 });
+
+        let c = parse("sim.{frame:07.2f}.bgeo");
+        assert_eq!(c.kind(), IndexKind::Subframe { padding: 2 });
+        assert_eq!(c.padding(), 4);
+
+        let c = crate::parse("render.####.exr [1-3]", None::<&str>).unwrap();
+        assert_eq!(c.indexes(), &[1, 2, 3]);
+        let c = crate::parse("render.$F4.exr", Some("{head}{houdini}{tail}")).unwrap();
+        assert_eq!(c.padding(), 4);
+        assert!(crate::parse("render.####.exr", Some("{head}{houdini}{tail}")).is_err());
+    }
+
+    #[test]
+    fn test_round_trip_styles() {
+        for (padding, indexes) in [
+            (0, vec![5, 10, 15]),
+            (1, vec![1, 2, 3]),
+            (4, vec![1001, 1003]),
+        ] {
+            let c = Collection::new("render.".to_string(), ".exr".to_string(), padding, indexes);
+            for style in PaddingStyle::ALL.iter() {
+                // Blender drops the dot before its token.
+                let mut expected = c.clone();
+                if *style == PaddingStyle::Blender {
+                    expected.head = "render".to_string();
+                }
+                let formatted = c.format_with::<&str>(None, *style).unwrap();
+                let parsed = crate::parse(&formatted, None::<&str>).unwrap();
+                assert_eq!(parsed, expected, "{}", formatted);
+
+                let fmt = format!("{{head}}{{{}}}{{tail}} [{{ranges}}]", style.key());
+                let formatted = c.format(Some(&fmt)).unwrap();
+                let parsed = crate::parse(&formatted, Some(&fmt)).unwrap();
+                assert_eq!(parsed, expected, "{}", formatted);
+            }
+        }
+
+        // Only the first padding key is read.
+        let c = Collection::new(
+            "render.".to_string(),
+            ".exr".to_string(),
+            4,
+            vec![1001, 1002],
+        );
+        for (fmt, style) in [
+            (
+                "{head}{padding}{tail} {hash} [{ranges}]",
+                PaddingStyle::Printf,
+            ),
+            (
+                "{head}{padding}{tail} {nuke} [{ranges}]",
+                PaddingStyle::Nuke,
+            ),
+            (
+                "{head}{nuke}{tail} {padding} {rv} [{ranges}]",
+                PaddingStyle::Printf,
+            ),
+        ] {
+            let formatted = c.format_with(Some(fmt), style).unwrap();
+            assert_eq!(
+                crate::parse(&formatted, Some(fmt)).unwrap(),
+                c,
+                "{}",
+                formatted
+            );
+        }
+        assert_eq!(
+            c.format_with(Some("{head}{padding}{tail} {nuke}"), PaddingStyle::Nuke)
+                .unwrap(),
+            "render.####.exr ####"
+        );
+    }
+
+    #[test]
+    fn test_blender() {
+        let c = Collection::new(
+            "render.".to_string(),
+            ".exr".to_string(),
+            4,
+            vec![1001, 1002],
+        );
+        assert_eq!(
+            c.format_with::<&str>(None, PaddingStyle::Blender).unwrap(),
+            "render####.exr [1001-1002]"
+        );
+        assert_eq!(
+            c.format(Some("{head}{blender}{tail} {head}{hash}{tail}"))
+                .unwrap(),
+            "render####.exr render.####.exr"
+        );
+        let c = crate::parse("render.####.exr", Some("{head}{blender}{tail}")).unwrap();
+        assert_eq!((c.head(), c.padding()), ("render.", 4));
+        assert!(c.match_item("render####.exr").unwrap().is_some());
+        assert!(c.match_item("render###.exr").unwrap().is_none());
+
+        let c = Collection::new("render_".to_string(), ".png".to_string(), 4, vec![1]);
+        assert_eq!(
+            c.format(Some("{head}{blender}{tail}")).unwrap(),
+            "render_####.png"
+        );
+    }
+
+    #[test]
+    fn test_match_styles() {
+        let c = Collection::new("render.".to_string(), ".exr".to_string(), 4, vec![1001]);
+        for item in [
+            "render.1002.exr",
+            "render.%04d.exr",
+            "render.####.exr",
+            "render.@@@@.exr",
+            "render.$F4.exr",
+            "render.1-10#.exr",
+            "render.{frame:04d}.exr",
+        ] {
+            assert!(c.match_item(item).unwrap().is_some(), "{}", item);
+        }
+        for item in ["render.###.exr", "render.%03d.exr", "render.$F3.exr"] {
+            assert!(c.match_item(item).unwrap().is_none(), "{}", item);
+        }
+        let mut c = c;
+        assert!(c.add("render.####.exr").is_err());
+    }
+}
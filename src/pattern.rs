@@ -0,0 +1,134 @@
+use crate::{
+    BLENDER_PATTERN, DIGITS_PATTERN, FRAME_PATTERN, HOUDINI_PATTERN, UDIM_PATTERN, VERSION_PATTERN,
+};
+
+// Common patterns for grouping items, to pass to assemble and its variants
+// in place of, or alongside, custom regexes:
+//
+//     let patterns = vec![Pattern::Frames.into(), "_(?P<index>\\d+)_".to_string()];
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Pattern {
+    // Every number, such as both 001 and 1001 of main_v001/render.1001.exr.
+    // A dash is the sign of a negative number only at the start of a name or
+    // after a dot or underscore, such as -0002 of sim.-0002.bgeo, so the dash
+    // of plate-1001.exr is not. This is the default pattern.
+    Digits,
+    // The frame number between the last two dots, such as 1001 of
+    // render.1001.exr.
+    Frames,
+    // The version number following a v, such as 001 of main_v001.
+    Versions,
+    // Four digit numbers from 1001 to 1999 that are not part of a longer
+    // number, such as 1011 of diffuse.1011.tx. Use with AssembleOptions::udim
+    // to group them into UDIM collections.
+    Udim,
+    // The number after a dot or underscore before the extensions, such as
+    // 0001 of name_0001.bgeo.sc, as written for a Houdini $F4 output.
+    Houdini,
+    // The number directly before the extension, such as 0001 of
+    // name0001.png, as written for a Blender output without a separator.
+    Blender,
+}
+
+impl Pattern {
+    // Return the regex of the pattern.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Pattern::Digits => DIGITS_PATTERN,
+            Pattern::Frames => FRAME_PATTERN,
+            Pattern::Versions => VERSION_PATTERN,
+            Pattern::Udim => UDIM_PATTERN,
+            Pattern::Houdini => HOUDINI_PATTERN,
+            Pattern::Blender => BLENDER_PATTERN,
+        }
+    }
+}
+
+impl From<Pattern> for String {
+    fn from(pattern: Pattern) -> String {
+        pattern.as_str().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble_with, AssembleOptions, IndexKind};
+
+    fn assemble_pattern(
+        files: &[&str],
+        patterns: Vec<String>,
+        options: &AssembleOptions,
+    ) -> Vec<String> {
+        let (collections, _remainder) = assemble_with(files, Some(patterns), options).unwrap();
+        collections
+            .iter()
+            .map(|c| c.format::<&str>(None).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_presets() {
+        let options = AssembleOptions::new();
+        let files = [
+            "main_v001/render.1001.exr",
+            "main_v001/render.1002.exr",
+            "main_v001/render.1003.exr",
+        ];
+        assert_eq!(
+            assemble_pattern(&files, vec![Pattern::Frames.into()], &options),
+            vec!["main_v001/render.%04d.exr [1001-1003]"]
+        );
+
+        let files = ["comp_v001.nk", "comp_v002.nk", "comp_v010.nk"];
+        assert_eq!(
+            assemble_pattern(&files, vec![Pattern::Versions.into()], &options),
+            vec!["comp_v%03d.nk [1-2, 10]"]
+        );
+
+        let files = ["sim_0001.bgeo.sc", "sim_0002.bgeo.sc", "sim_0003.bgeo.sc"];
+        assert_eq!(
+            assemble_pattern(&files, vec![Pattern::Houdini.into()], &options),
+            vec!["sim_%04d.bgeo.sc [1-3]"]
+        );
+
+        let files = ["shot2_0001.png", "shot2_0002.png", "shot2_0003.png"];
+        assert_eq!(
+            assemble_pattern(&files, vec![Pattern::Blender.into()], &options),
+            vec!["shot2_%04d.png [1-3]"]
+        );
+
+        let files = ["diffuse.1001.tx", "diffuse.1002.tx", "diffuse.10011.tx"];
+        let options = AssembleOptions::new().minimum_items(2).udim(true);
+        let (collections, remainder) =
+            assemble_with(&files, Some(vec![Pattern::Udim.into()]), &options).unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].kind, IndexKind::Udim);
+        assert_eq!(remainder, vec!["diffuse.10011.tx"]);
+    }
+
+    #[test]
+    fn test_presets_with_custom_pattern() {
+        let files = [
+            "main_v001/render.1001.exr",
+            "main_v001/render.1002.exr",
+            "main_v001/render.1003.exr",
+            "cache_a_01_.abc",
+            "cache_a_02_.abc",
+            "cache_a_03_.abc",
+        ];
+        let patterns = vec![
+            Pattern::Frames.into(),
+            "_(?P<index>(?P<padding>0*)\\d+)_".to_string(),
+        ];
+        assert_eq!(
+            assemble_pattern(&files, patterns, &AssembleOptions::new()),
+            vec![
+                "cache_a_%02d_.abc [1-3]",
+                "main_v001/render.%04d.exr [1001-1003]"
+            ]
+        );
+    }
+}
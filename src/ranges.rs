@@ -0,0 +1,640 @@
+use std::cmp::Ordering;
+use std::iter::StepBy;
+use std::ops::RangeInclusive;
+
+// A set of integers stored as ascending runs of values a constant step apart,
+// so a run of consecutive values, or of every other value, takes the same
+// space as one value and operations take time proportional to the number of
+// runs.
+//
+// Each run is its first value, last value and step. Runs are kept in one form
+// for each set, so equal sets have equal runs: ranges of consecutive values
+// are runs with a step of 1, and values with no neighbour in the set are
+// joined from the left into runs of values a constant step apart, starting a
+// new run from the last value of a run of two values it does not continue.
+// So 1, 3, 5, 6 and 9 are the runs (1, 3, 2), (5, 6, 1) and (9, 9, 1), and 1,
+// 3, 6 and 9 are the runs (1, 1, 1) and (3, 9, 3).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub(crate) struct RangeSet {
+    runs: Vec<(i64, i64, i64)>,
+}
+
+impl RangeSet {
+    pub(crate) fn new() -> RangeSet {
+        RangeSet::default()
+    }
+
+    // Return the set of values, which may be in any order and repeat.
+    pub(crate) fn from_values(mut values: Vec<i64>) -> RangeSet {
+        values.sort_unstable();
+        values.dedup();
+        let mut set = RangeSet::new();
+        for value in values {
+            set.push(value, value, 1);
+        }
+        set
+    }
+
+    // Return the set of the values of runs of a first value, last value and
+    // step, which may be in any order and overlap. Runs that end before they
+    // start or do not have a positive step are empty.
+    pub(crate) fn from_runs<I: IntoIterator<Item = (i64, i64, i64)>>(runs: I) -> RangeSet {
+        let mut runs: Vec<(i64, i64, i64)> = runs
+            .into_iter()
+            .filter(|(start, end, step)| start <= end && *step > 0)
+            .collect();
+        runs.sort_unstable();
+        let mut set = RangeSet::new();
+        for (start, end, step) in runs {
+            match set.last() {
+                Some(last) if start <= last => {
+                    let mut run = RangeSet::new();
+                    run.push(start, end, step);
+                    set = set.combine(&run, |a, b| a || b);
+                }
+                _ => set.push(start, end, step),
+            }
+        }
+        set
+    }
+
+    // Return the runs of the set in ascending order, as their first value,
+    // last value and step.
+    pub(crate) fn runs(&self) -> &[(i64, i64, i64)] {
+        &self.runs
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.runs
+            .iter()
+            .map(|(start, end, step)| {
+                ((*end as i128 - *start as i128) / *step as i128 + 1) as usize
+            })
+            .sum()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    pub(crate) fn first(&self) -> Option<i64> {
+        self.runs.first().map(|(start, _, _)| *start)
+    }
+
+    pub(crate) fn last(&self) -> Option<i64> {
+        self.runs.last().map(|(_, end, _)| *end)
+    }
+
+    pub(crate) fn contains(&self, value: i64) -> bool {
+        let position = self.runs.partition_point(|(_, end, _)| *end < value);
+        self.runs
+            .get(position)
+            .is_some_and(|(start, _, step)| *start <= value && on_step(*start, value, *step))
+    }
+
+    // Add value to the set, returning whether it was not already in it.
+    pub(crate) fn insert(&mut self, value: i64) -> bool {
+        if self.contains(value) {
+            return false;
+        }
+        match self.last() {
+            Some(last) if value < last => {
+                *self = self.combine(&RangeSet::from_values(vec![value]), |a, b| a || b)
+            }
+            _ => self.push(value, value, 1),
+        }
+        true
+    }
+
+    // Remove value from the set, returning whether it was in it.
+    pub(crate) fn remove(&mut self, value: i64) -> bool {
+        if !self.contains(value) {
+            return false;
+        }
+        *self = self.combine(&RangeSet::from_values(vec![value]), |a, b| a && !b);
+        true
+    }
+
+    // Return the values of the set in ascending order.
+    pub(crate) fn iter(&self) -> Iter<'_> {
+        Values::new(self.runs.iter().copied())
+    }
+
+    pub(crate) fn to_vec(&self) -> Vec<i64> {
+        self.iter().collect()
+    }
+
+    // Append the values from start to end, step apart, which are all greater
+    // than the values of the set. End is moved back to the last value of the
+    // run.
+    fn push(&mut self, start: i64, end: i64, step: i64) {
+        if start > end {
+            return;
+        }
+        if step == 1 {
+            return self.push_range(start, end);
+        }
+        // Whatever the runs before them, the run of the third value has the
+        // step, so the values after it extend that run.
+        let end = last_value(start, end, step);
+        let mut value = start;
+        for _ in 0..3 {
+            self.push_range(value, value);
+            if value == end {
+                return;
+            }
+            value += step;
+        }
+        if let Some(run) = self.runs.last_mut() {
+            run.1 = end;
+        }
+    }
+
+    // Append the consecutive values from start to end, which are all greater
+    // than the values of the set.
+    fn push_range(&mut self, start: i64, end: i64) {
+        if let Some((first, last, step)) = self.runs.last_mut() {
+            let is_range = *step == 1 && first < last;
+            if last.checked_add(1) == Some(start) {
+                if is_range {
+                    *last = end;
+                    return;
+                }
+                // The last value now has a neighbour, so leaves its run to
+                // start the range, and the runs before it are as they were
+                // before it was added.
+                let joined = *last;
+                if *first + *step == *last {
+                    let first = *first;
+                    self.runs.pop();
+                    self.push_range(first, first);
+                } else if first == last {
+                    self.runs.pop();
+                } else {
+                    *last -= *step;
+                }
+                self.runs.push((joined, end, 1));
+                return;
+            }
+            if start == end && !is_range {
+                match start.checked_sub(*last) {
+                    Some(difference) if first == last => {
+                        *last = start;
+                        *step = difference;
+                        return;
+                    }
+                    Some(difference) if difference == *step => {
+                        *last = start;
+                        return;
+                    }
+                    // A run of two values the value does not continue is
+                    // split, so its last value may start a run with it.
+                    Some(difference) if *first + *step == *last => {
+                        let joined = *last;
+                        *last = *first;
+                        *step = 1;
+                        self.runs.push((joined, start, difference));
+                        return;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.runs.push((start, end, 1));
+    }
+
+    // Append the values of runs a and b, both within the values between the
+    // same boundaries, for which keep returns true.
+    fn push_both(
+        &mut self,
+        a: (i64, i64, i64),
+        b: (i64, i64, i64),
+        keep: &impl Fn(bool, bool) -> bool,
+    ) {
+        if a == b {
+            if keep(true, true) {
+                self.push(a.0, a.1, a.2);
+            }
+            return;
+        }
+        // Runs with the same step on different values share none of them, and
+        // both span the values between the boundaries, so runs of every other
+        // value fill them.
+        if a.2 == b.2 && !on_step(a.0, b.0, a.2) {
+            match (keep(true, false), keep(false, true)) {
+                (true, true) if a.2 == 2 => self.push(a.0.min(b.0), a.1.max(b.1), 1),
+                (true, true) => self.push_merged(a, b, keep),
+                (true, false) => self.push(a.0, a.1, a.2),
+                (false, true) => self.push(b.0, b.1, b.2),
+                (false, false) => {}
+            }
+            return;
+        }
+        // A single value splits the other run in two.
+        if a.0 == a.1 {
+            return self.push_split(b, a.0, |in_run, in_value| keep(in_value, in_run));
+        }
+        if b.0 == b.1 {
+            return self.push_split(a, b.0, keep);
+        }
+        // Otherwise one run may hold every value from the first to the last
+        // value of the other.
+        let holds = |x: (i64, i64, i64), y: (i64, i64, i64)| x.2 == 1 && x.0 <= y.0 && y.1 <= x.1;
+        let (all, some, keep_some, keep_rest) = if holds(a, b) {
+            (a, b, keep(true, true), keep(true, false))
+        } else if holds(b, a) {
+            (b, a, keep(true, true), keep(false, true))
+        } else {
+            return self.push_merged(a, b, keep);
+        };
+        match (keep_some, keep_rest) {
+            (true, true) => self.push(all.0, all.1, 1),
+            (true, false) => self.push(some.0, some.1, some.2),
+            (false, true) => {
+                let (start, end, step) = some;
+                if all.0 < start {
+                    self.push(all.0, start - 1, 1);
+                }
+                if step == 2 {
+                    self.push(start + 1, end - 1, 2);
+                } else {
+                    for value in (start..end).step_by(step as usize) {
+                        self.push(value + 1, value + step - 1, 1);
+                    }
+                }
+                if end < all.1 {
+                    self.push(end + 1, all.1, 1);
+                }
+            }
+            (false, false) => {}
+        }
+    }
+
+    // Append the values of run and the single value for which keep returns
+    // true, given whether each is in run and is the value.
+    fn push_split(&mut self, run: (i64, i64, i64), value: i64, keep: impl Fn(bool, bool) -> bool) {
+        let (start, end, step) = run;
+        let keep_run = keep(true, false);
+        if keep_run && start < value {
+            self.push(start, end.min(value - 1), step);
+        }
+        let in_run = start <= value && value <= end && on_step(start, value, step);
+        if keep(in_run, true) {
+            self.push(value, value, 1);
+        }
+        if keep_run && value < end {
+            let next = match value < start {
+                true => start,
+                false => last_value(start, value, step) + step,
+            };
+            self.push(next, end, step);
+        }
+    }
+
+    // Append the values of runs a and b for which keep returns true, a value
+    // at a time.
+    fn push_merged(
+        &mut self,
+        a: (i64, i64, i64),
+        b: (i64, i64, i64),
+        keep: &impl Fn(bool, bool) -> bool,
+    ) {
+        let mut a = (a.0..=a.1).step_by(a.2 as usize).peekable();
+        let mut b = (b.0..=b.1).step_by(b.2 as usize).peekable();
+        loop {
+            let (in_a, in_b, value) = match (a.peek(), b.peek()) {
+                (Some(x), Some(y)) if x == y => (true, true, *x),
+                (Some(x), Some(y)) if x < y => (true, false, *x),
+                (Some(x), None) => (true, false, *x),
+                (_, Some(y)) => (false, true, *y),
+                (None, None) => return,
+            };
+            if in_a {
+                a.next();
+            }
+            if in_b {
+                b.next();
+            }
+            if keep(in_a, in_b) {
+                self.push(value, value, 1);
+            }
+        }
+    }
+
+    // Return the set of values for which keep returns true, given whether
+    // each is in this set and other.
+    pub(crate) fn combine(&self, other: &RangeSet, keep: impl Fn(bool, bool) -> bool) -> RangeSet {
+        // Every run holds either none of the values between consecutive
+        // boundaries or all of them that are on its step.
+        let mut boundaries: Vec<i128> = self
+            .runs
+            .iter()
+            .chain(other.runs.iter())
+            .flat_map(|(start, end, _)| [*start as i128, *end as i128 + 1])
+            .collect();
+        boundaries.sort_unstable();
+        boundaries.dedup();
+
+        let mut set = RangeSet::new();
+        let (mut a, mut b) = (0, 0);
+        for pair in boundaries.windows(2) {
+            let (low, high) = (pair[0] as i64, (pair[1] - 1) as i64);
+            while self.runs.get(a).is_some_and(|run| run.1 < low) {
+                a += 1;
+            }
+            while other.runs.get(b).is_some_and(|run| run.1 < low) {
+                b += 1;
+            }
+            let run_a = self.runs.get(a).and_then(|run| within(*run, low, high));
+            let run_b = other.runs.get(b).and_then(|run| within(*run, low, high));
+            match (run_a, run_b) {
+                (Some(run), None) if keep(true, false) => set.push(run.0, run.1, run.2),
+                (None, Some(run)) if keep(false, true) => set.push(run.0, run.1, run.2),
+                (Some(run_a), Some(run_b)) => set.push_both(run_a, run_b, &keep),
+                _ => {}
+            }
+        }
+        set
+    }
+
+    // Return the values between the first and last value of the set that are
+    // a multiple of step from the values around them and not in the set. With
+    // a step of 1 these are all of the missing values.
+    pub(crate) fn gaps(&self, step: i64) -> RangeSet {
+        let mut set = RangeSet::new();
+        let mut previous: Option<i64> = None;
+        for &(start, end, run_step) in self.runs.iter() {
+            if let Some(previous) = previous {
+                set.push(previous + step, start - step, step);
+            }
+            if run_step == 2 * step {
+                set.push(start + step, end - step, run_step);
+            } else if run_step > step {
+                for value in (start..end).step_by(run_step as usize) {
+                    set.push(value + step, value + run_step - step, step);
+                }
+            }
+            previous = Some(end);
+        }
+        set
+    }
+}
+
+// Return the last value of the run from start to end, step apart.
+fn last_value(start: i64, end: i64, step: i64) -> i64 {
+    let (start, step) = (start as i128, step as i128);
+    (start + (end as i128 - start) / step * step) as i64
+}
+
+// Return whether value is a multiple of step from start.
+fn on_step(start: i64, value: i64, step: i64) -> bool {
+    (value as i128 - start as i128) % step as i128 == 0
+}
+
+// Return the values of run between low and high, where the run starts at or
+// after low only if it starts at low. A single value has a step of 1.
+fn within(run: (i64, i64, i64), low: i64, high: i64) -> Option<(i64, i64, i64)> {
+    let (start, end, step) = run;
+    if start > high {
+        return None;
+    }
+    let (wide_start, wide_step) = (start as i128, step as i128);
+    let first = wide_start + (low as i128 - wide_start + wide_step - 1) / wide_step * wide_step;
+    let last = last_value(start, end.min(high), step) as i128;
+    match first.cmp(&last) {
+        Ordering::Less => Some((first as i64, last as i64, step)),
+        Ordering::Equal => Some((first as i64, last as i64, 1)),
+        Ordering::Greater => None,
+    }
+}
+
+// Sets are ordered as their values in ascending order are, comparing a run at
+// a time rather than every value.
+impl Ord for RangeSet {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (mut runs_a, mut runs_b) = (self.runs.iter().copied(), other.runs.iter().copied());
+        let (mut a, mut b) = (runs_a.next(), runs_b.next());
+        loop {
+            let (run_a, run_b) = match (a, b) {
+                (Some(run_a), Some(run_b)) => (run_a, run_b),
+                (a, b) => return a.is_some().cmp(&b.is_some()),
+            };
+            if run_a.0 != run_b.0 {
+                return run_a.0.cmp(&run_b.0);
+            }
+            // Skip the values both sets share from here, which are the rest
+            // of the shorter run if the steps are equal, or else the first.
+            let shared = match run_a.2 == run_b.2 {
+                true => run_a.1.min(run_b.1),
+                false => run_a.0,
+            };
+            a = after(run_a, shared).or_else(|| runs_a.next());
+            b = after(run_b, shared).or_else(|| runs_b.next());
+        }
+    }
+}
+
+impl PartialOrd for RangeSet {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Return the rest of run after value, a value of the run.
+fn after(run: (i64, i64, i64), value: i64) -> Option<(i64, i64, i64)> {
+    let (_, end, step) = run;
+    match value < end {
+        true => Some((value + step, end, step)),
+        false => None,
+    }
+}
+
+// Iterator over the values of runs in ascending order.
+#[derive(Debug)]
+pub(crate) struct Values<I> {
+    runs: I,
+    current: StepBy<RangeInclusive<i64>>,
+}
+
+pub(crate) type Iter<'a> = Values<std::iter::Copied<std::slice::Iter<'a, (i64, i64, i64)>>>;
+pub(crate) type IntoIter = Values<std::vec::IntoIter<(i64, i64, i64)>>;
+
+impl<I: Iterator<Item = (i64, i64, i64)>> Values<I> {
+    fn new(runs: I) -> Values<I> {
+        #[allow(clippy::reversed_empty_ranges)]
+        let current = (1..=0).step_by(1);
+        Values { runs, current }
+    }
+}
+
+impl<I: Iterator<Item = (i64, i64, i64)>> Iterator for Values<I> {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        loop {
+            if let Some(value) = self.current.next() {
+                return Some(value);
+            }
+            let (start, end, step) = self.runs.next()?;
+            self.current = (start..=end).step_by(step as usize);
+        }
+    }
+}
+
+impl IntoIterator for RangeSet {
+    type Item = i64;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        Values::new(self.runs.into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Return pseudo-random sets of values from 0 to 39 with a simple linear
+    // congruential generator.
+    fn random_sets(count: usize) -> Vec<Vec<i64>> {
+        let mut state: u64 = 1;
+        (0..count)
+            .map(|_| {
+                (0..40)
+                    .filter(|_| {
+                        state = state
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        (state >> 33).is_multiple_of(3)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_range_set() {
+        let mut set = RangeSet::from_values(vec![5, 1, 2, 3, 3, 7, 8]);
+        assert_eq!(set.runs(), &[(1, 3, 1), (5, 5, 1), (7, 8, 1)]);
+        assert_eq!(set.len(), 6);
+        assert!(set.contains(2) && set.contains(8));
+        assert!(!set.contains(4) && !set.contains(0) && !set.contains(9));
+
+        assert!(set.insert(4));
+        assert!(set.insert(6));
+        assert!(!set.insert(6));
+        assert_eq!(set.runs(), &[(1, 8, 1)]);
+        assert!(set.remove(4));
+        assert!(set.remove(1));
+        assert!(!set.remove(1));
+        assert_eq!(set.runs(), &[(2, 3, 1), (5, 8, 1)]);
+        assert_eq!(set.to_vec(), vec![2, 3, 5, 6, 7, 8]);
+        assert_eq!(set.gaps(1).to_vec(), vec![4]);
+
+        let set = RangeSet::from_values(vec![1, 3, 5, 6, 9, 11, 13, 20, 27]);
+        assert_eq!(set.runs(), &[(1, 3, 2), (5, 6, 1), (9, 13, 2), (20, 27, 7)]);
+        assert_eq!(
+            RangeSet::from_runs([(20, 27, 7), (1, 5, 2), (11, 11, 1), (6, 6, 1), (9, 13, 2)]),
+            set
+        );
+        assert!(set.contains(11) && !set.contains(12) && !set.contains(21));
+
+        let huge = RangeSet::from_runs([(0, 999_999_999, 1), (2_000_000_000, 2_000_000_000, 1)]);
+        assert_eq!(huge.len(), 1_000_000_001);
+        assert_eq!(huge.gaps(1).runs(), &[(1_000_000_000, 1_999_999_999, 1)]);
+        assert_eq!(
+            RangeSet::from_runs([(5, 9, 1), (1, 3, 1), (4, 4, 1), (8, 12, 1)]).runs(),
+            &[(1, 12, 1)]
+        );
+
+        let stepped = RangeSet::from_runs([(1, 1_000_000_000_000, 2)]);
+        assert_eq!(stepped.runs(), &[(1, 999_999_999_999, 2)]);
+        assert_eq!(stepped.len(), 500_000_000_000);
+        assert!(stepped.contains(999_999_999_999) && !stepped.contains(2));
+        assert!(stepped.gaps(2).is_empty());
+        assert_eq!(stepped.gaps(1).runs(), &[(2, 999_999_999_998, 2)]);
+        let mut holes = stepped.clone();
+        assert!(holes.remove(5));
+        assert_eq!(
+            holes.runs(),
+            &[(1, 1, 1), (3, 3, 1), (7, 999_999_999_999, 2)]
+        );
+        assert_eq!(holes.gaps(2).runs(), &[(5, 5, 1)]);
+        assert_eq!(
+            RangeSet::from_runs([(1, 1, 1), (3, 20_000_001, 19_999_998)])
+                .gaps(2)
+                .runs(),
+            &[(5, 19_999_999, 2)]
+        );
+    }
+
+    #[test]
+    fn test_combine() {
+        let a = RangeSet::from_runs([(1, 10, 1), (20, 30, 1)]);
+        let b = RangeSet::from_runs([(5, 25, 1), (i64::MAX - 1, i64::MAX, 1)]);
+        assert_eq!(
+            a.combine(&b, |a, b| a || b).runs(),
+            &[(1, 30, 1), (i64::MAX - 1, i64::MAX, 1)]
+        );
+        assert_eq!(
+            a.combine(&b, |a, b| a && b).runs(),
+            &[(5, 10, 1), (20, 25, 1)]
+        );
+        assert_eq!(
+            a.combine(&b, |a, b| a && !b).runs(),
+            &[(1, 4, 1), (26, 30, 1)]
+        );
+        assert_eq!(
+            a.combine(&b, |a, b| a != b).runs(),
+            &[
+                (1, 4, 1),
+                (11, 19, 1),
+                (26, 30, 1),
+                (i64::MAX - 1, i64::MAX, 1)
+            ]
+        );
+
+        let evens = RangeSet::from_runs([(0, 1_000_000_000_000, 2)]);
+        let odds = RangeSet::from_runs([(1, 999_999_999_999, 2)]);
+        assert_eq!(
+            evens.combine(&odds, |a, b| a || b).runs(),
+            &[(0, 1_000_000_000_000, 1)]
+        );
+        let all = RangeSet::from_runs([(0, 1_000_000_000_000, 1)]);
+        assert_eq!(all.combine(&evens, |a, b| a && !b), odds);
+        assert_eq!(all.combine(&odds, |a, b| a && b), odds);
+
+        // Every combination of small sets agrees with combining their values.
+        let sets = random_sets(12);
+        let keeps: [fn(bool, bool) -> bool; 4] =
+            [|a, b| a || b, |a, b| a && b, |a, b| a && !b, |a, b| a != b];
+        for a in sets.iter() {
+            for b in sets.iter() {
+                let (set_a, set_b) = (
+                    RangeSet::from_values(a.clone()),
+                    RangeSet::from_values(b.clone()),
+                );
+                for keep in keeps.iter() {
+                    let expected: Vec<i64> = (0..40)
+                        .filter(|value| keep(a.contains(value), b.contains(value)))
+                        .collect();
+                    let combined = set_a.combine(&set_b, keep);
+                    assert_eq!(combined.to_vec(), expected);
+                    assert_eq!(combined, RangeSet::from_values(expected));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ord() {
+        let mut sets = random_sets(30);
+        sets.extend([vec![], vec![1, 2], vec![1, 2, 3], vec![1, 3], vec![1, 3, 5]]);
+        for a in sets.iter() {
+            for b in sets.iter() {
+                let ranges = |values: &Vec<i64>| RangeSet::from_values(values.clone());
+                assert_eq!(ranges(a).cmp(&ranges(b)), a.cmp(b), "{:?} {:?}", a, b);
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::os;
+use crate::Collection;
+
+// An item that is not in any collection, with the reason it is not.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Remainder {
+    pub item: String,
+    pub reason: RemainderReason,
+}
+
+// Reason an item is not in any collection.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum RemainderReason {
+    // No pattern matched a number in the item.
+    NoMatch,
+    // The item belonged to a collection with fewer indexes than
+    // minimum_items. Of the collections the item belonged to, this is the one
+    // with the most indexes.
+    BelowMinimumItems(Collection),
+    // A pattern matched a number in the item that is too large to be an
+    // index, between head and tail with padding.
+    IndexOverflow {
+        head: String,
+        tail: String,
+        padding: i32,
+    },
+}
+
+impl Remainder {
+    // Return the path of the item. Items from assemble_paths with bytes that
+    // are not valid UTF-8 are converted back to those bytes.
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(os::decode(&self.item))
+    }
+}
+
+impl fmt::Display for RemainderReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RemainderReason::NoMatch => write!(f, "no number matched"),
+            RemainderReason::BelowMinimumItems(collection) => match collection.format::<&str>(None)
+            {
+                Ok(collection) => write!(f, "too few items in {}", collection),
+                Err(_) => write!(f, "too few items"),
+            },
+            RemainderReason::IndexOverflow { .. } => write!(f, "number too large to be an index"),
+        }
+    }
+}
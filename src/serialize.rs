@@ -0,0 +1,428 @@
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::collections::BTreeMap;
+
+use crate::ranges::RangeSet;
+use crate::{Collection, FrameRange, FrameSet, IndexKind, MultiCollection};
+
+// A run of values a constant step apart, as its first value, last value and
+// step.
+type Run = (i64, i64, i64);
+
+// Collections are serialized as their head, tail, padding, kind of index and
+// the runs of indexes a constant step apart, as their first index, last index
+// and step, rather than every index, so a long sequence with few holes stays
+// small. In JSON:
+//
+//     {
+//         "head": "render.",
+//         "tail": ".exr",
+//         "padding": 4,
+//         "kind": "frame",
+//         "ranges": [[1001, 1050, 1], [1060, 1060, 1], [1100, 1200, 5]]
+//     }
+//
+// The kind is "frame", "udim" or {"subframe": {"padding": 2}}. Runs are in
+// ascending order without overlapping, and subframe indexes and steps are
+// fixed point as returned by Collection::indexes. Deserializing accepts runs
+// in any order and overlapping, but not runs that end before they start, do
+// not have a positive step or have indexes wider than the padding.
+#[derive(serde::Serialize)]
+struct CollectionRef<'a> {
+    head: &'a str,
+    tail: &'a str,
+    padding: i32,
+    kind: IndexKind,
+    ranges: &'a [Run],
+}
+
+#[derive(serde::Deserialize)]
+struct CollectionData {
+    head: String,
+    tail: String,
+    padding: i32,
+    kind: IndexKind,
+    ranges: Vec<Run>,
+}
+
+impl Serialize for Collection {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CollectionRef {
+            head: &self.head,
+            tail: &self.tail,
+            padding: self.padding,
+            kind: self.kind,
+            ranges: self.indexes.runs(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Collection {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = CollectionData::deserialize(deserializer)?;
+        let mut collection = Collection::new(data.head, data.tail, data.padding, vec![]);
+        collection.indexes = from_runs(data.ranges)?;
+        collection
+            .try_with_kind(data.kind)
+            .map_err(de::Error::custom)
+    }
+}
+
+// Return the set of the values of runs, or an error for the first run that
+// ends before it starts or does not have a positive step.
+fn from_runs<E: de::Error>(runs: Vec<Run>) -> Result<RangeSet, E> {
+    if let Some((start, end, _)) = runs.iter().find(|(start, end, _)| end < start) {
+        return Err(E::custom(format!(
+            "range {}-{} ends before it starts",
+            start, end
+        )));
+    }
+    if let Some((start, end, step)) = runs.iter().find(|(_, _, step)| *step <= 0) {
+        return Err(E::custom(format!(
+            "range {}-{}x{} does not have a positive step",
+            start, end, step
+        )));
+    }
+    Ok(RangeSet::from_runs(runs))
+}
+
+// Frame sets are serialized as the runs of frames, as the indexes of
+// collections are, such as [[1001, 1050, 1], [1060, 1060, 1]].
+impl Serialize for FrameSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.frames.runs().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FrameSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let runs = Vec::<Run>::deserialize(deserializer)?;
+        Ok(FrameSet {
+            frames: from_runs(runs)?,
+        })
+    }
+}
+
+// Frame ranges are serialized as their start, end and step, such as
+// {"start": 1100, "end": 1200, "step": 5}. Deserializing rejects ranges
+// FrameRange::new does, and moves the end back to the last frame.
+#[derive(serde::Deserialize)]
+struct FrameRangeData {
+    start: i64,
+    end: i64,
+    step: i64,
+}
+
+impl<'de> Deserialize<'de> for FrameRange {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = FrameRangeData::deserialize(deserializer)?;
+        FrameRange::new(data.start, data.end, data.step).map_err(de::Error::custom)
+    }
+}
+
+// Multi-dimensional collections are serialized as their head, separators,
+// tail, paddings and blocks of indexes. Each block is the runs of numbers of
+// each axis, as the indexes of collections are, and holds every index that
+// takes a number from each, so wedges 0-9 of frames 1001-1100 are one block:
+//
+//     {
+//         "head": "wedge_",
+//         "separators": ["."],
+//         "tail": ".bgeo",
+//         "paddings": [2, 4],
+//         "blocks": [[[[0, 9, 1]], [[1001, 1100, 1]]]]
+//     }
+//
+// Deserializing accepts blocks that overlap, but rejects blocks without runs
+// for each padding or separators that do not fall between the paddings.
+#[derive(serde::Serialize)]
+struct MultiCollectionRef<'a> {
+    head: &'a str,
+    separators: &'a [String],
+    tail: &'a str,
+    paddings: &'a [i32],
+    blocks: Vec<Vec<&'a [Run]>>,
+}
+
+#[derive(serde::Deserialize)]
+struct MultiCollectionData {
+    head: String,
+    separators: Vec<String>,
+    tail: String,
+    paddings: Vec<i32>,
+    blocks: Vec<Vec<Vec<Run>>>,
+}
+
+impl Serialize for MultiCollection {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let indexes: Vec<&[i64]> = self.indexes.iter().map(|index| index.as_slice()).collect();
+        let blocks = blocks(&indexes);
+        MultiCollectionRef {
+            head: &self.head,
+            separators: &self.separators,
+            tail: &self.tail,
+            paddings: &self.paddings,
+            blocks: blocks
+                .iter()
+                .map(|block| block.iter().map(|axis| axis.runs()).collect())
+                .collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+// Return blocks of the numbers of each axis that together hold indexes, all
+// of which have the same number of axes. The indexes that share every number
+// but the last are grouped by the set of their last numbers, and the numbers
+// before them split into blocks in turn.
+fn blocks(indexes: &[&[i64]]) -> Vec<Vec<RangeSet>> {
+    let axis = match indexes.first() {
+        Some(index) if !index.is_empty() => index.len() - 1,
+        _ => return vec![],
+    };
+    let mut lasts: BTreeMap<&[i64], Vec<i64>> = BTreeMap::new();
+    for index in indexes {
+        lasts.entry(&index[..axis]).or_default().push(index[axis]);
+    }
+    let mut prefixes: BTreeMap<RangeSet, Vec<&[i64]>> = BTreeMap::new();
+    for (prefix, last) in lasts {
+        prefixes
+            .entry(RangeSet::from_values(last))
+            .or_default()
+            .push(prefix);
+    }
+    let mut blocks = vec![];
+    for (last, prefixes) in prefixes {
+        match axis {
+            0 => blocks.push(vec![last]),
+            _ => {
+                for mut block in self::blocks(&prefixes) {
+                    block.push(last.clone());
+                    blocks.push(block);
+                }
+            }
+        }
+    }
+    blocks
+}
+
+impl<'de> Deserialize<'de> for MultiCollection {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = MultiCollectionData::deserialize(deserializer)?;
+        if data.separators.len() + 1 != data.paddings.len() {
+            return Err(de::Error::custom(format!(
+                "expected {} separators between {} paddings",
+                data.paddings.len().saturating_sub(1),
+                data.paddings.len()
+            )));
+        }
+        let mut indexes = vec![];
+        for block in data.blocks {
+            if block.len() != data.paddings.len() {
+                return Err(de::Error::custom(format!(
+                    "block {:?} does not have runs for {} paddings",
+                    block,
+                    data.paddings.len()
+                )));
+            }
+            // Every index of the block takes a number from each axis in turn.
+            let mut block_indexes = vec![vec![]];
+            for runs in block {
+                let numbers = from_runs(runs)?;
+                block_indexes = block_indexes
+                    .into_iter()
+                    .flat_map(|index: Vec<i64>| {
+                        numbers.iter().map(move |number| {
+                            let mut index = index.clone();
+                            index.push(number);
+                            index
+                        })
+                    })
+                    .collect();
+            }
+            indexes.extend(block_indexes);
+        }
+        Ok(MultiCollection::new(
+            data.head,
+            data.separators,
+            data.tail,
+            data.paddings,
+            indexes,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        assemble_multi, assemble_with_reasons, AssembleOptions, Collection, FrameRange, FrameSet,
+        IndexKind, MatchSelection, MultiCollection, Pattern, Remainder, RemainderReason, Subframe,
+        UdimTile,
+    };
+
+    #[test]
+    fn test_collection_json() {
+        let c = Collection::new(
+            "render.".to_string(),
+            ".exr".to_string(),
+            4,
+            vec![1001, 1002, 1003, 1005],
+        );
+        let json = serde_json::to_string(&c).unwrap();
+        assert_eq!(
+            json,
+            r#"{"head":"render.","tail":".exr","padding":4,"kind":"frame","ranges":[[1001,1003,1],[1005,1005,1]]}"#
+        );
+        assert_eq!(serde_json::from_str::<Collection>(&json).unwrap(), c);
+
+        let c = Collection::new("sim.".to_string(), ".bgeo".to_string(), 4, vec![100125])
+            .with_kind(IndexKind::Subframe { padding: 2 });
+        let json = serde_json::to_string(&c).unwrap();
+        assert!(json.contains(r#""kind":{"subframe":{"padding":2}}"#));
+        assert_eq!(serde_json::from_str::<Collection>(&json).unwrap(), c);
+
+        let invalid = [
+            r#"{"head":"a.","tail":"","padding":4,"kind":"frame","ranges":[[5,1,1]]}"#,
+            r#"{"head":"a.","tail":"","padding":4,"kind":"frame","ranges":[[1,5,0]]}"#,
+            r#"{"head":"a.","tail":"","padding":4,"kind":"frame","ranges":[[1,10000,1]]}"#,
+        ];
+        for json in invalid.iter() {
+            assert!(serde_json::from_str::<Collection>(json).is_err());
+        }
+    }
+
+    #[test]
+    fn test_assembly_json() {
+        let files = [
+            "a.001.exr",
+            "a.002.exr",
+            "a.003.exr",
+            "b.001.exr",
+            "notes.txt",
+        ];
+        let options = AssembleOptions::new()
+            .minimum_items(2)
+            .selection(MatchSelection::Rightmost);
+        let patterns = vec![Pattern::Frames.into()];
+        let (collections, remainder) =
+            assemble_with_reasons(&files, Some(patterns), &options).unwrap();
+        assert_eq!(remainder.len(), 2);
+
+        let json = serde_json::to_string(&(&collections, &remainder)).unwrap();
+        let (parsed_collections, parsed_remainder): (Vec<Collection>, Vec<Remainder>) =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed_collections, collections);
+        assert_eq!(parsed_remainder, remainder);
+
+        let json = serde_json::to_string(&RemainderReason::NoMatch).unwrap();
+        assert_eq!(json, r#""no_match""#);
+
+        let json = serde_json::to_string(&options).unwrap();
+        assert_eq!(
+            serde_json::from_str::<AssembleOptions>(&json).unwrap(),
+            options
+        );
+        let options: AssembleOptions =
+            serde_json::from_str(r#"{"minimum_items":2,"udim":true}"#).unwrap();
+        assert_eq!(options, AssembleOptions::new().minimum_items(2).udim(true));
+
+        let json = serde_json::to_string(&Pattern::Houdini).unwrap();
+        assert_eq!(json, r#""houdini""#);
+        assert_eq!(
+            serde_json::from_str::<Pattern>(&json).unwrap(),
+            Pattern::Houdini
+        );
+    }
+
+    #[test]
+    fn test_frames_json() {
+        let frames: FrameSet = "1001-1050,1060,1100-1110x5,1000000-1000000000000x2"
+            .parse()
+            .unwrap();
+        let json = serde_json::to_string(&frames).unwrap();
+        assert_eq!(
+            json,
+            "[[1001,1050,1],[1060,1060,1],[1100,1110,5],[1000000,1000000000000,2]]"
+        );
+        assert_eq!(serde_json::from_str::<FrameSet>(&json).unwrap(), frames);
+        let frames: FrameSet = serde_json::from_str("[[1,3,1],[7,11,2],[2,5,1]]").unwrap();
+        assert_eq!(frames.to_string(), "1-5,7-11x2");
+        assert!(serde_json::from_str::<FrameSet>("[[5,1,1]]").is_err());
+        assert!(serde_json::from_str::<FrameSet>("[[1,5,-1]]").is_err());
+
+        let range = FrameRange::new(1100, 1203, 5).unwrap();
+        let json = serde_json::to_string(&range).unwrap();
+        assert_eq!(json, r#"{"start":1100,"end":1200,"step":5}"#);
+        assert_eq!(serde_json::from_str::<FrameRange>(&json).unwrap(), range);
+        for json in [
+            r#"{"start":1,"end":5,"step":0}"#,
+            r#"{"start":5,"end":1,"step":1}"#,
+        ] {
+            assert!(serde_json::from_str::<FrameRange>(json).is_err());
+        }
+
+        let subframe = Subframe::from_index(100125, 2);
+        let json = serde_json::to_string(&subframe).unwrap();
+        assert_eq!(serde_json::from_str::<Subframe>(&json).unwrap(), subframe);
+        let tile = UdimTile::from_udim(1012).unwrap();
+        let json = serde_json::to_string(&tile).unwrap();
+        assert_eq!(json, r#"{"u":1,"v":1}"#);
+        assert_eq!(serde_json::from_str::<UdimTile>(&json).unwrap(), tile);
+    }
+
+    #[test]
+    fn test_multi_collection_json() {
+        let files = [
+            "wedge_00.1001.bgeo",
+            "wedge_00.1002.bgeo",
+            "wedge_01.1001.bgeo",
+            "wedge_01.1002.bgeo",
+        ];
+        let patterns = vec![
+            "_(?P<index_0>(?P<padding_0>0*)\\d+)\\.(?P<index_1>(?P<padding_1>0*)\\d+)\\."
+                .to_string(),
+        ];
+        let (collections, _) = assemble_multi(&files, patterns, &AssembleOptions::new()).unwrap();
+        assert_eq!(collections.len(), 1);
+        let json = serde_json::to_string(&collections).unwrap();
+        assert!(json.contains(r#""blocks":[[[[0,1,1]],[[1001,1002,1]]]]"#));
+        assert_eq!(
+            serde_json::from_str::<Vec<MultiCollection>>(&json).unwrap(),
+            collections
+        );
+
+        // Wedges 0-2 of frames 1001-1100 and wedge 3 of frames 1001-1050.
+        let mut indexes = vec![];
+        for wedge in 0..4 {
+            let last = if wedge < 3 { 1100 } else { 1050 };
+            indexes.extend((1001..=last).map(|frame| vec![wedge, frame]));
+        }
+        let c = MultiCollection::new(
+            "w_".to_string(),
+            vec![".".to_string()],
+            ".bgeo".to_string(),
+            vec![2, 4],
+            indexes,
+        );
+        let json = serde_json::to_string(&c).unwrap();
+        assert!(json
+            .ends_with(r#""blocks":[[[[3,3,1]],[[1001,1050,1]]],[[[0,2,1]],[[1001,1100,1]]]]}"#));
+        assert_eq!(serde_json::from_str::<MultiCollection>(&json).unwrap(), c);
+
+        let json = r#"{"head":"w_","separators":["."],"tail":".bgeo","paddings":[2,4],
+            "blocks":[[[[1,1,1]],[[1001,1001,1]]],[[[0,1,1]],[[1001,1001,1]]]]}"#;
+        let c: MultiCollection = serde_json::from_str(json).unwrap();
+        assert_eq!(c.indexes(), &[vec![0, 1001], vec![1, 1001]]);
+
+        let invalid = [
+            r#"{"head":"w_","separators":[],"tail":"","paddings":[2,4],"blocks":[]}"#,
+            r#"{"head":"w_","separators":["."],"tail":"","paddings":[2,4],"blocks":[[[[1,1,1]]]]}"#,
+            r#"{"head":"w_","separators":["."],"tail":"","paddings":[2,4],
+                "blocks":[[[[1,1,1]],[[5,1,1]]]]}"#,
+        ];
+        for json in invalid.iter() {
+            assert!(serde_json::from_str::<MultiCollection>(json).is_err());
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+
+// An index of a subframe collection split into its whole frame and fractional
+// part. The fraction is in units of the last of precision digits and has the
+// same sign as the frame, so 1001.25 is frame 1001 with fraction 25 at
+// precision 2, and -1.5 is frame -1 with fraction -5 at precision 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Subframe {
+    pub frame: i64,
+    pub fraction: i64,
+    pub precision: i32,
+}
+
+impl Subframe {
+    // Split a fixed point index with precision fractional digits.
+    pub fn from_index(index: i64, precision: i32) -> Subframe {
+        let scale = scale(precision);
+        Subframe {
+            frame: index / scale,
+            fraction: index % scale,
+            precision,
+        }
+    }
+
+    // Return the fixed point index the subframe is stored as.
+    pub fn to_index(&self) -> i64 {
+        self.frame * scale(self.precision) + self.fraction
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.frame as f64 + self.fraction as f64 / scale(self.precision) as f64
+    }
+}
+
+// Display as a decimal without trailing zeros, such as 1001.25 or 1002.
+impl fmt::Display for Subframe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.frame < 0 || self.fraction < 0 {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", self.frame.unsigned_abs())?;
+        if self.fraction != 0 {
+            let fraction = format!(
+                "{:0>precision$}",
+                self.fraction.unsigned_abs(),
+                precision = self.precision.max(0) as usize
+            );
+            write!(f, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        Ok(())
+    }
+}
+
+// Return the number of fixed point units in a whole frame.
+pub(crate) fn scale(precision: i32) -> i64 {
+    10_i64.pow(precision.max(0) as u32)
+}
+
+// Parse a decimal such as "1001.25" or "-3" into a fixed point index with
+// precision fractional digits. Returns None if it has more fractional digits
+// than precision.
+pub(crate) fn parse_decimal(value: &str, precision: i32) -> Option<i64> {
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value),
+    };
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if fraction.len() > precision.max(0) as usize {
+        return None;
+    }
+    let whole = whole.parse::<i64>().ok()?;
+    let fraction = match fraction {
+        "" => 0,
+        fraction => fraction.parse::<i64>().ok()? * scale(precision - fraction.len() as i32),
+    };
+    let index = whole.checked_mul(scale(precision))?.checked_add(fraction)?;
+    match negative {
+        true => Some(-index),
+        false => Some(index),
+    }
+}
+
+// Return the number of fractional digits in a decimal such as "1001.25".
+pub(crate) fn decimal_precision(value: &str) -> i32 {
+    match value.split_once('.') {
+        Some((_, fraction)) => fraction.len() as i32,
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subframe() {
+        let s = Subframe::from_index(100125, 2);
+        assert_eq!(s.frame, 1001);
+        assert_eq!(s.fraction, 25);
+        assert_eq!(s.to_index(), 100125);
+        assert_eq!(s.to_f64(), 1001.25);
+        assert_eq!(s.to_string(), "1001.25");
+
+        let s = Subframe::from_index(-15, 1);
+        assert_eq!(s.frame, -1);
+        assert_eq!(s.fraction, -5);
+        assert_eq!(s.to_string(), "-1.5");
+
+        assert_eq!(Subframe::from_index(-5, 1).to_string(), "-0.5");
+        assert_eq!(Subframe::from_index(100200, 2).to_string(), "1002");
+        assert_eq!(Subframe::from_index(1500, 3).to_string(), "1.5");
+    }
+
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!(parse_decimal("1001.25", 2), Some(100125));
+        assert_eq!(parse_decimal("1001.5", 3), Some(1001500));
+        assert_eq!(parse_decimal("1002", 2), Some(100200));
+        assert_eq!(parse_decimal("-0.5", 1), Some(-5));
+        assert_eq!(parse_decimal("1001.255", 2), None);
+        assert_eq!(decimal_precision("1001.255"), 3);
+        assert_eq!(decimal_precision("1001"), 0);
+    }
+}
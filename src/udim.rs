@@ -0,0 +1,57 @@
+// A UDIM tile decomposed into its zero based u column and v row, so 1001 is
+// u 0 v 0 and 1012 is u 1 v 1. Mari style u1_v1 names are one based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UdimTile {
+    pub u: i64,
+    pub v: i64,
+}
+
+pub(crate) const UDIM_START: i64 = 1001;
+pub(crate) const UDIM_END: i64 = 1999;
+pub(crate) const UDIM_TOKEN: &str = "<UDIM>";
+pub(crate) const UVTILE_TOKEN: &str = "u<U>_v<V>";
+
+impl UdimTile {
+    // Return the tile of a UDIM number, or None if it is not a tile in
+    // 1001-1999.
+    pub fn from_udim(udim: i64) -> Option<UdimTile> {
+        if !is_udim(udim) {
+            return None;
+        }
+        Some(UdimTile {
+            u: (udim - UDIM_START) % 10,
+            v: (udim - UDIM_START) / 10,
+        })
+    }
+
+    pub fn to_udim(&self) -> i64 {
+        UDIM_START + self.u + self.v * 10
+    }
+
+    // Return the Mari style name of the tile, such as u1_v1 for 1001.
+    pub fn to_uvtile(&self) -> String {
+        format!("u{}_v{}", self.u + 1, self.v + 1)
+    }
+}
+
+pub(crate) fn is_udim(index: i64) -> bool {
+    (UDIM_START..=UDIM_END).contains(&index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_udim_tile() {
+        assert_eq!(UdimTile::from_udim(1001), Some(UdimTile { u: 0, v: 0 }));
+        assert_eq!(UdimTile::from_udim(1010), Some(UdimTile { u: 9, v: 0 }));
+        assert_eq!(UdimTile::from_udim(1012), Some(UdimTile { u: 1, v: 1 }));
+        assert_eq!(UdimTile::from_udim(1999), Some(UdimTile { u: 8, v: 99 }));
+        assert_eq!(UdimTile::from_udim(1000), None);
+        assert_eq!(UdimTile::from_udim(2001), None);
+        assert_eq!(UdimTile { u: 1, v: 1 }.to_udim(), 1012);
+        assert_eq!(UdimTile { u: 0, v: 0 }.to_uvtile(), "u1_v1");
+    }
+}